@@ -13,6 +13,7 @@
     with pkgs; [
       git
       cargo-make
+      softhsm
 
     ] ++ lib.optionals stdenv.isDarwin
       (with darwin.apple_sdk; [
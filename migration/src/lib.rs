@@ -4,17 +4,44 @@ mod m20240714_063538_users;
 mod m20240714_083102_keys;
 mod m20240714_102309_shares;
 mod m20240714_173831_logs;
+mod m20241018_090000_keys_allow_raw_hash;
+mod m20241018_100000_keys_threshold;
+mod m20241018_110000_share_owner_recovery;
+mod m20241018_120000_keys_commitments;
+mod m20241018_130000_keys_key_type;
+mod m20241018_140000_keys_local_dek;
+mod m20241018_150000_cloud_shares;
+mod m20241018_160000_key_versions;
+mod m20241018_170000_keys_allow_signer_cache;
+mod sqlite;
 
 pub struct Migrator;
 
+/// Enums are Postgres enum types, SQLite has none and stores them in string columns.
+fn has_enum_types(manager: &SchemaManager) -> bool {
+    manager.get_database_backend() == sea_orm::DatabaseBackend::Postgres
+}
+
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             Box::new(m20240714_063538_users::Migration),
             Box::new(m20240714_083102_keys::Migration),
-            Box::new(m20240714_102309_shares::Migration),
+            Box::new(sqlite::WithSqlite(
+                m20240714_102309_shares::Migration,
+                sqlite::SharesMigration,
+            )),
             Box::new(m20240714_173831_logs::Migration),
+            Box::new(m20241018_090000_keys_allow_raw_hash::Migration),
+            Box::new(m20241018_100000_keys_threshold::Migration),
+            Box::new(m20241018_110000_share_owner_recovery::Migration),
+            Box::new(m20241018_120000_keys_commitments::Migration),
+            Box::new(m20241018_130000_keys_key_type::Migration),
+            Box::new(m20241018_140000_keys_local_dek::Migration),
+            Box::new(m20241018_150000_cloud_shares::Migration),
+            Box::new(m20241018_160000_key_versions::Migration),
+            Box::new(m20241018_170000_keys_allow_signer_cache::Migration),
         ]
     }
 }
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // One column per statement, SQLite can not alter several at once.
+        for column in [Keys::TotalShares, Keys::Threshold] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Keys::Table)
+                        .add_column(ColumnDef::new(column).integer().not_null().default(3))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Keys::TotalShares, Keys::Threshold] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Keys::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Keys {
+    Table,
+    TotalShares,
+    Threshold,
+}
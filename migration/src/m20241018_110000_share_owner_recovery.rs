@@ -0,0 +1,29 @@
+use crate::extension::postgres::Type;
+use crate::has_enum_types;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !has_enum_types(manager) {
+            return Ok(());
+        }
+
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(Alias::new("share_owner"))
+                    .add_value(Alias::new("recovery"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres can not drop a value from an enum type
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use crate::extension::postgres::Type;
+use crate::has_enum_types;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let mut key_type = ColumnDef::new(Keys::KeyType);
+
+        if has_enum_types(manager) {
+            manager
+                .create_type(
+                    Type::create()
+                        .as_enum(Alias::new("key_type"))
+                        .values([Alias::new("ecdsa"), Alias::new("schnorr")])
+                        .to_owned(),
+                )
+                .await?;
+
+            key_type.custom(Alias::new("key_type"));
+        } else {
+            key_type.string();
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Keys::Table)
+                    .add_column(key_type.not_null().default("ecdsa"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Keys::Table)
+                    .drop_column(Keys::KeyType)
+                    .to_owned(),
+            )
+            .await?;
+
+        if !has_enum_types(manager) {
+            return Ok(());
+        }
+
+        manager
+            .drop_type(Type::drop().name(Alias::new("key_type")).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Keys {
+    Table,
+    KeyType,
+}
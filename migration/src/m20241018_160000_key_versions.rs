@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Keys::Table)
+                    .add_column(ColumnDef::new(Keys::CloudVersion).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(KeyVersions::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(KeyVersions::KeyId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(KeyVersions::CloudVersion)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(KeyVersions::LocalKey).text().not_null())
+                    .col(ColumnDef::new(KeyVersions::LocalIndex).text().not_null())
+                    .col(ColumnDef::new(KeyVersions::LocalDek).text().null())
+                    .col(
+                        ColumnDef::new(KeyVersions::Commitments)
+                            .json_binary()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(KeyVersions::TotalShares)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(KeyVersions::Threshold).integer().not_null())
+                    .col(ColumnDef::new(KeyVersions::ShareIds).json().not_null())
+                    .col(
+                        ColumnDef::new(KeyVersions::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(KeyVersions::KeyId)
+                            .col(KeyVersions::CloudVersion),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(KeyVersions::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Keys::Table)
+                    .drop_column(Keys::CloudVersion)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Keys {
+    Table,
+    CloudVersion,
+}
+
+#[derive(Iden)]
+enum KeyVersions {
+    Table,
+    KeyId,
+    CloudVersion,
+    LocalKey,
+    LocalIndex,
+    LocalDek,
+    Commitments,
+    TotalShares,
+    Threshold,
+    ShareIds,
+    CreatedAt,
+}
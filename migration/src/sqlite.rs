@@ -0,0 +1,100 @@
+use crate::has_enum_types;
+use sea_orm_migration::prelude::*;
+
+/// Runs a migration written for Postgres enum types on Postgres and its SQLite counterpart
+/// elsewhere. It is recorded under the name of the Postgres migration, so databases that
+/// already applied it are left alone and the original migration stays untouched.
+pub struct WithSqlite<P, S>(pub P, pub S);
+
+impl<P: MigrationName, S> MigrationName for WithSqlite<P, S> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: MigrationTrait, S: MigrationTrait> MigrationTrait for WithSqlite<P, S> {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match has_enum_types(manager) {
+            true => self.0.up(manager).await,
+            false => self.1.up(manager).await,
+        }
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        match has_enum_types(manager) {
+            true => self.0.down(manager).await,
+            false => self.1.down(manager).await,
+        }
+    }
+}
+
+/// `m20240714_102309_shares` with string columns instead of the `share_owner` and
+/// `share_status` enum types.
+pub struct SharesMigration;
+
+impl MigrationName for SharesMigration {
+    fn name(&self) -> &str {
+        "m20240714_102309_shares"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for SharesMigration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Shares::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Shares::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Shares::KeyId).uuid().not_null())
+                    .col(ColumnDef::new(Shares::Secret).string().not_null())
+                    .col(ColumnDef::new(Shares::UserIndex).string().not_null())
+                    .col(
+                        ColumnDef::new(Shares::Owner)
+                            .string()
+                            .not_null()
+                            .default("unknown"),
+                    )
+                    .col(
+                        ColumnDef::new(Shares::Status)
+                            .string()
+                            .not_null()
+                            .default("unknown"),
+                    )
+                    .col(
+                        ColumnDef::new(Shares::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Shares::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Shares::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Shares {
+    Table,
+    Id,
+    KeyId,
+    Secret,
+    UserIndex,
+    Owner,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
+use migration::{Migrator, MigratorTrait};
 use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 use tracing::log;
-use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
 
 use crate::config::Config;
+use crate::helpers::share_path::SharePath;
+use crate::services::kek::Kek;
+use crate::services::signer_cache::SignerCache;
+use crate::services::storage::{self, ShareStorage};
+use crate::services::vault::Vault;
 
 #[derive(Clone)]
 pub struct AppData {
+    config: Config,
     db: DatabaseConnection,
-    vault: Arc<VaultClient>,
+    storage: Arc<dyn ShareStorage>,
+    kek: Option<Arc<Kek>>,
+    vault: Option<Arc<Vault>>,
+    share_path: SharePath,
+    signer_cache: Option<Arc<SignerCache>>,
 }
 
 impl AppData {
@@ -18,28 +28,78 @@ impl AppData {
         opt.sqlx_logging(true)
             .sqlx_logging_level(log::LevelFilter::Debug);
 
+        // SQLite allows a single writer, and every connection to an in-memory database
+        // opens a database of its own.
+        if config.database_url.starts_with("sqlite:") {
+            opt.max_connections(1);
+        }
+
         let db = Database::connect(opt).await.expect("Connection error");
 
-        let vault = VaultClient::new(
-            VaultClientSettingsBuilder::default()
-                .address(config.vault_storage.to_string())
-                .token(config.vault_token.to_string())
-                .build()
-                .expect("Vault client settings error"),
-        )
-        .expect("Vault client error");
+        let vault = match config.vault_storage {
+            Some(_) => Some(Vault::connect(config).await.expect("Vault error")),
+            None => None,
+        };
+
+        let storage =
+            storage::from_config(config, vault.clone(), &db).expect("Share storage error");
+
+        let share_path = SharePath::from_config(config).expect("Share path template error");
+
+        let kek = Kek::from_config(config, vault.clone()).expect("Key-encryption key error");
+
+        if kek.is_none() {
+            log::warn!("No key-encryption key configured, local shares are stored in plaintext");
+        }
 
         AppData {
+            config: config.clone(),
             db,
-            vault: Arc::new(vault),
+            storage,
+            kek: kek.map(Arc::new),
+            vault,
+            share_path,
+            signer_cache: SignerCache::from_config(config).map(Arc::new),
         }
     }
 
+    /// Migrated app data of [`Config::in_memory`], for tests without Postgres or Vault. Every
+    /// call gets a database of its own, gone once the app data is dropped.
+    pub async fn in_memory() -> Self {
+        let app_data = Self::new(&Config::in_memory()).await;
+
+        Migrator::up(app_data.get_db_connection(), None)
+            .await
+            .expect("migration error");
+
+        app_data
+    }
+
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn get_db_connection(&self) -> &DatabaseConnection {
         &self.db
     }
 
-    pub fn get_vault_client(&self) -> Arc<VaultClient> {
+    pub fn get_share_storage(&self) -> Arc<dyn ShareStorage> {
+        self.storage.clone()
+    }
+
+    pub fn get_kek(&self) -> Option<Arc<Kek>> {
+        self.kek.clone()
+    }
+
+    pub fn get_vault(&self) -> Option<Arc<Vault>> {
         self.vault.clone()
     }
+
+    pub fn get_share_path(&self) -> &SharePath {
+        &self.share_path
+    }
+
+    pub fn get_signer_cache(&self) -> Option<Arc<SignerCache>> {
+        self.signer_cache.clone()
+    }
 }
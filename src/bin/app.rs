@@ -4,7 +4,7 @@ use dotenv::dotenv;
 use tracing::info;
 use tracing::level_filters::LevelFilter;
 
-use kms::{handlers, AppData, Config};
+use kms::{encrypt_local_shares, handlers, AppData, Config};
 use migration::{Migrator, MigratorTrait};
 
 #[tokio::main]
@@ -24,6 +24,10 @@ async fn main() {
         .await
         .expect("migration error");
 
+    encrypt_local_shares(&app_data)
+        .await
+        .expect("local share encryption error");
+
     let port = config.clone().port.unwrap_or(String::from("8080"));
 
     info!("Starting web_app on port: {port}");
@@ -3,11 +3,107 @@ use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
+    /// Postgres, or SQLite for local development and single-node deployments, e.g.
+    /// `sqlite://kms.db?mode=rwc` or `sqlite::memory:`.
     pub database_url: String,
     pub port: Option<String>,
     pub cors_origin_url: Option<String>,
-    pub vault_storage: String,
-    pub vault_token: String,
+    /// Vault address, required by the Vault backed share storages and the transit
+    /// key-encryption key.
+    pub vault_storage: Option<String>,
+    pub vault_token: Option<String>,
+    #[serde(default)]
+    pub vault_auth: VaultAuthMethod,
+    /// Mount of the AppRole or Kubernetes auth method, `approle` or `kubernetes` by default.
+    pub vault_auth_mount: Option<String>,
+    pub vault_role_id: Option<String>,
+    pub vault_secret_id: Option<String>,
+    pub vault_kubernetes_role: Option<String>,
+    /// Service account token presented to Vault, the token mounted into the pod by default.
+    pub vault_kubernetes_token_path: Option<String>,
+    /// KV2 mount of the `vault` share storage, `secret` by default.
+    pub vault_mount: Option<String>,
+    /// Vault Enterprise namespace of every request.
+    pub vault_namespace: Option<String>,
+    /// Path of cloud shares, with `{env}`, `{user_id}`, `{key_id}` and `{code}` placeholders.
+    /// A random `{code}` segment is appended when missing.
+    pub share_path_template: Option<String>,
+    /// Substituted for `{env}` in `share_path_template`.
+    pub environment: Option<String>,
+    /// Superseded sharings kept per key for `/keys/rollback`, none by default. Only
+    /// refreshes and reshares asking for `keep_previous` on a versioned share storage keep
+    /// one, the oldest beyond this limit are destroyed.
+    pub key_versions_retained: Option<usize>,
+    pub max_shares: Option<usize>,
+    pub min_threshold: Option<usize>,
+    #[serde(default)]
+    pub signing_mode: SigningMode,
+    /// Seconds a reconstructed signer is kept in memory for repeated signing with the same
+    /// shares, disabled when unset. Only applies to the `reconstruct` signing mode and to keys
+    /// generated with `allow_signer_cache`.
+    ///
+    /// Revocations and share replacements only drop the signers cached by the replica that
+    /// handled them. With several replicas, another replica keeps signing with a revoked share
+    /// until its entry expires, so keep the TTL short or leave the cache off.
+    pub signer_cache_ttl: Option<u64>,
+    /// Signatures per cached signer before its shares are restored again, 100 by default.
+    pub signer_cache_max_uses: Option<u32>,
+    /// Hex encoded 32 byte key-encryption key for local shares.
+    pub local_kek: Option<String>,
+    /// Vault transit key used as key-encryption key instead of `local_kek`.
+    pub vault_transit_key: Option<String>,
+    /// Mount of the transit engine holding `vault_transit_key` and
+    /// `share_storage_transit_key`, `transit` by default.
+    pub vault_transit_mount: Option<String>,
+    #[serde(default)]
+    pub share_storage: ShareStorageKind,
+    /// Directory of the `file` share storage.
+    pub share_storage_dir: Option<String>,
+    /// Hex encoded 32 byte key encrypting the files of the `file` share storage.
+    pub share_storage_key: Option<String>,
+    /// Vault transit key of the `transit_postgres` share storage.
+    pub share_storage_transit_key: Option<String>,
+    /// Path of the PKCS#11 library of the `pkcs11` share storage.
+    pub pkcs11_module: Option<String>,
+    /// Label of the token holding the wrapping key.
+    pub pkcs11_token: Option<String>,
+    pub pkcs11_pin: Option<String>,
+    /// Label of the AES wrapping key, generated on the token if missing.
+    pub pkcs11_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningMode {
+    /// Interpolates the private key from the shares for every signature.
+    #[default]
+    Reconstruct,
+    /// In-process additive signing, not a threshold mode: the shares are not added up to
+    /// the private key, but this process still holds all of them, see
+    /// [`crate::services::additive::AdditiveSigner`].
+    Additive,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultAuthMethod {
+    /// Static `vault_token`, renewed while renewable but never replaced.
+    #[default]
+    Token,
+    AppRole,
+    Kubernetes,
+}
+
+/// Backend of the cloud shares, see [`crate::services::storage`].
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareStorageKind {
+    #[default]
+    Vault,
+    TransitPostgres,
+    File,
+    Memory,
+    Pkcs11,
 }
 
 impl Default for Config {
@@ -15,3 +111,15 @@ impl Default for Config {
         from_env::<Config>().expect("Provide missing environment variables for Config")
     }
 }
+
+impl Config {
+    /// SQLite in memory and the memory share storage, every other setting at its default.
+    /// Nothing is read from the environment.
+    pub fn in_memory() -> Self {
+        serde_json::from_value(serde_json::json!({
+            "database_url": "sqlite::memory:",
+            "share_storage": "memory",
+        }))
+        .expect("In-memory config is valid")
+    }
+}
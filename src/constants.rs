@@ -1,2 +1,12 @@
 pub static MASTER_KEY: &str = "x-master-key";
 pub static SECRET_KEY: &str = "x-secret-key";
+pub static PASSPHRASE: &str = "x-passphrase";
+pub static MAX_BATCH_SIZE: usize = 1000;
+
+pub static DEFAULT_SHARES: usize = 3;
+pub static DEFAULT_THRESHOLD: usize = 3;
+pub static DEFAULT_MAX_SHARES: usize = 10;
+pub static DEFAULT_SIGNER_CACHE_MAX_USES: u32 = 100;
+/// Cloud and local shares are held by the server, so at least one user share
+/// must always take part in restoring a key.
+pub static MIN_THRESHOLD: usize = 3;
@@ -1,5 +1,12 @@
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+
+use crate::AppData;
+
+pub async fn healthcheck_handler(app_data: web::Data<AppData>) -> HttpResponse {
+    if let Some(Err(err)) = app_data.get_vault().map(|vault| vault.client()) {
+        return HttpResponse::ServiceUnavailable().json(json!({"error": err.to_string()}));
+    }
 
-pub async fn healthcheck_handler() -> HttpResponse {
     HttpResponse::Ok().body("OK")
 }
@@ -1,31 +1,124 @@
 use actix_web::{web, HttpRequest, HttpResponse};
 use alloy::signers::local::PrivateKeySigner;
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
 use num_bigint::BigUint;
 use num_traits::Num;
+use sea_orm::TransactionTrait;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 use uuid::Uuid;
-use vaultrs::kv2;
 
-use crate::constants::{MASTER_KEY, SECRET_KEY};
-use crate::helpers::generate_code::{generate_code, generate_random};
-use crate::models::shares::SharesOwner;
-use crate::queries::keys::{create_key, get_key_by_id, CreateOrUpdateKey, KeyErrors};
+use crate::constants::{
+    DEFAULT_MAX_SHARES, DEFAULT_SHARES, DEFAULT_THRESHOLD, MASTER_KEY, MIN_THRESHOLD, PASSPHRASE,
+    SECRET_KEY,
+};
+use crate::helpers::generate_code::generate_random;
+use crate::helpers::local_share::{open_local_share, seal_local_share, LocalShareError};
+use crate::helpers::restore_shares::{restore_shares, RestoreSharesError};
+use crate::helpers::share_token::{ShareFormat, ShareToken, ShareTokenError};
+use crate::models::keys::{KeyType, Model as KeyModel};
+use crate::models::shares::{SharesOwner, SharesStatus};
+use crate::queries::key_versions::{
+    delete_key_version, delete_key_versions, get_key_version, prune_key_versions, save_key_version,
+    KeyVersionErrors,
+};
+use crate::queries::keys::{
+    create_key, get_key_by_id, update_key_shares, CreateOrUpdateKey, KeyErrors, UpdateKeyShares,
+};
 use crate::queries::logs::{create_log, CreateLog};
 use crate::queries::shares::{
-    create_share, get_share_by_id, get_share_by_secret, revoke_share_by_id, CreateOrUpdateShare,
-    ShareErrors,
+    consume_recovery_shares, create_share, get_granted_shares_by_key_id, get_share_by_id,
+    get_share_by_secret, grant_shares_by_ids, revoke_share_by_id, revoke_shares_by_key_id,
+    CreateOrUpdateShare, ShareErrors,
 };
 use crate::queries::users::{get_user_by_secret, UserErrors};
-use crate::services::polynomial::{Polynomial, Share, ShareStore};
+use crate::services::frost::xonly_secret;
+use crate::services::polynomial::{Commitments, Polynomial, Share, ShareStore};
+use crate::services::storage::{read_cloud_share, StorageError};
 use crate::AppData;
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeysGenerateRequest {
+    #[serde(default)]
+    pub allow_raw_hash: bool,
+    /// Lets a reconstructed signer of the key be cached when `signer_cache_ttl` is set.
+    #[serde(default)]
+    pub allow_signer_cache: bool,
+    pub shares: Option<usize>,
+    pub threshold: Option<usize>,
+    #[serde(default)]
+    pub recovery_shares: usize,
+    #[serde(default)]
+    pub key_type: KeyType,
+    #[serde(default)]
+    pub format: ShareFormat,
+    /// Wraps the returned user shares, they then only work together with the `x-passphrase`
+    /// header.
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeysGrantRequest {
+    #[serde(default)]
+    pub format: ShareFormat,
+    /// Alternative to the `x-secret-key` header, e.g. for shares transcribed as words.
+    pub secret_key: Option<String>,
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysShareResponse {
+    pub key: String,
+    pub id: Uuid,
+    /// The share this one replaces, set by a refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeysGenerateResponse {
     pub key: String,
     pub id: Uuid,
+    pub key_id: Uuid,
+    #[serde(default)]
+    pub shares: Vec<KeysShareResponse>,
+    #[serde(default)]
+    pub recovery_shares: Vec<KeysShareResponse>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeysRefreshRequest {
+    #[serde(default)]
+    pub recovery_shares: usize,
+    #[serde(default)]
+    pub format: ShareFormat,
+    pub passphrase: Option<String>,
+    /// Keeps the replaced sharing for `/keys/rollback`, see [`keys_rollback_handler`].
+    #[serde(default)]
+    pub keep_previous: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeysReshareRequest {
+    pub shares: usize,
+    pub threshold: usize,
+    #[serde(default)]
+    pub recovery_shares: usize,
+    #[serde(default)]
+    pub format: ShareFormat,
+    pub passphrase: Option<String>,
+    /// Keeps the replaced sharing for `/keys/rollback`, see [`keys_rollback_handler`].
+    #[serde(default)]
+    pub keep_previous: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysCheckResponse {
+    pub key_id: Uuid,
+    pub valid: bool,
+    pub share: bool,
+    pub local: bool,
+    pub cloud: bool,
+    pub address: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +126,105 @@ pub struct KeysRevokeRequest {
     pub id: Uuid,
 }
 
-pub async fn keys_generate_handler(req: HttpRequest, app_data: web::Data<AppData>) -> HttpResponse {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysRollbackRequest {
+    pub key_id: Uuid,
+    /// Version of the cloud share to return to.
+    pub version: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysRollbackResponse {
+    pub key_id: Uuid,
+    pub version: i64,
+    /// Shares of the restored version, granted again.
+    pub share_ids: Vec<Uuid>,
+    pub revoked_share_ids: Vec<Uuid>,
+}
+
+fn restore_error_response(err: RestoreSharesError) -> HttpResponse {
+    match err {
+        RestoreSharesError::ShareNotFound(_) => HttpResponse::NotFound().finish(),
+        RestoreSharesError::DecodeError(
+            ShareTokenError::PassphraseRequired | ShareTokenError::Passphrase,
+        ) => HttpResponse::Unauthorized().json(serde_json::json!({"error": err.to_string()})),
+        RestoreSharesError::DecodeError(ShareTokenError::KeyDerivation) => {
+            HttpResponse::InternalServerError().finish()
+        }
+        RestoreSharesError::DecodeError(_)
+        | RestoreSharesError::NotEnoughShares(_)
+        | RestoreSharesError::KeyMismatch
+        | RestoreSharesError::TokenMismatch
+        | RestoreSharesError::LocalShare(LocalShareError::LegacyIndex) => {
+            HttpResponse::BadRequest().json(serde_json::json!({"error": err.to_string()}))
+        }
+        RestoreSharesError::Revoked | RestoreSharesError::RecoveryShare => {
+            HttpResponse::Unauthorized().finish()
+        }
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn set_share_error_response(err: StorageError) -> HttpResponse {
+    match err {
+        StorageError::Collision(_) => {
+            HttpResponse::Conflict().json(serde_json::json!({"error": err.to_string()}))
+        }
+        _ => HttpResponse::InternalServerError().body(format!("Error setting secret: {}", err)),
+    }
+}
+
+fn validate_share_counts(
+    app_data: &AppData,
+    total_shares: usize,
+    threshold: usize,
+    recovery_shares: usize,
+) -> Result<(), HttpResponse> {
+    let max_shares = app_data
+        .get_config()
+        .max_shares
+        .unwrap_or(DEFAULT_MAX_SHARES);
+    let min_threshold = app_data
+        .get_config()
+        .min_threshold
+        .unwrap_or(MIN_THRESHOLD)
+        .max(MIN_THRESHOLD);
+
+    if threshold < min_threshold
+        || threshold > total_shares
+        || total_shares + recovery_shares > max_shares
+    {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "Threshold must be between {min_threshold} and shares, shares must not exceed {max_shares}"
+            )
+        })));
+    }
+
+    Ok(())
+}
+
+fn validate_passphrase(passphrase: Option<&str>) -> Result<(), HttpResponse> {
+    if passphrase.is_some_and(str::is_empty) {
+        return Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Passphrase must not be empty"})));
+    }
+
+    Ok(())
+}
+
+pub async fn keys_generate_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: Option<web::Json<KeysGenerateRequest>>,
+) -> HttpResponse {
+    let body = body.map(|body| body.into_inner()).unwrap_or_default();
+
     let Some(Ok(master_key)) = req.headers().get(MASTER_KEY).map(|header| header.to_str()) else {
         return HttpResponse::Unauthorized().finish();
     };
 
-    let user = match get_user_by_secret(&master_key, app_data.get_db_connection()).await {
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
         Ok(user) => user,
         Err(UserErrors::NotFound(_)) => {
             return HttpResponse::Unauthorized().finish();
@@ -48,84 +234,181 @@ pub async fn keys_generate_handler(req: HttpRequest, app_data: web::Data<AppData
         }
     };
 
+    let total_shares = body.shares.unwrap_or(DEFAULT_SHARES);
+    let threshold = body.threshold.unwrap_or(DEFAULT_THRESHOLD);
+
+    if let Err(response) =
+        validate_share_counts(&app_data, total_shares, threshold, body.recovery_shares)
+    {
+        return response;
+    }
+
+    if let Err(response) = validate_passphrase(body.passphrase.as_deref()) {
+        return response;
+    }
+
     let private_key = generate_random();
-    let Ok(signer) = PrivateKeySigner::from_slice(private_key.as_slice()) else {
-        return HttpResponse::InternalServerError().finish();
-    };
 
-    let secret = BigUint::from_bytes_be(private_key.as_slice());
+    let (secret, address) = match body.key_type {
+        KeyType::Ecdsa => {
+            let Ok(signer) = PrivateKeySigner::from_slice(private_key.as_slice()) else {
+                return HttpResponse::InternalServerError().finish();
+            };
+
+            (
+                BigUint::from_bytes_be(private_key.as_slice()),
+                signer.address().to_string(),
+            )
+        }
+        KeyType::Schnorr => {
+            let Some((secret, public_key)) = xonly_secret(private_key.as_slice()) else {
+                return HttpResponse::InternalServerError().finish();
+            };
+
+            (secret, hex::encode(public_key))
+        }
+    };
 
     let poly = Polynomial::new();
 
-    let shares = poly
-        .generate_shares(&secret, 5, 3)
-        .iter()
-        .map(Into::into)
-        .collect::<Vec<ShareStore>>();
+    let (shares, commitments) =
+        poly.generate_shares(&secret, total_shares + body.recovery_shares, threshold);
+
+    let shares = shares.iter().map(Into::into).collect::<Vec<ShareStore>>();
 
     debug!("Shares: {:?}", shares);
 
-    let path = generate_code();
+    let key_id = Uuid::new_v4();
 
-    if let Err(err) = kv2::set(
-        app_data.get_vault_client().as_ref(),
-        "secret",
-        &path,
-        &shares[0],
-    )
-    .await
-    {
-        return HttpResponse::InternalServerError().body(format!("Error setting secret: {}", err));
-    }
+    let local = match seal_local_share(&app_data, &key_id, &shares[1]).await {
+        Ok(local) => local,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error encrypting local share: {}", err));
+        }
+    };
+
+    let path = app_data.get_share_path().render(&user.id, &key_id);
+
+    let cloud_version = match app_data.get_share_storage().set(&path, &shares[0]).await {
+        Ok(version) => version.map(|version| version as i64),
+        Err(err) => return set_share_error_response(err),
+    };
 
     let key = CreateOrUpdateKey {
+        id: key_id,
         user_id: user.id,
-        local_key: shares[1].y.clone(),
-        local_index: shares[1].y.clone(),
-        cloud_key: path,
-        address: signer.address(),
+        local_key: local.local_key,
+        local_index: local.local_index,
+        local_dek: local.local_dek,
+        cloud_key: path.clone(),
+        cloud_version,
+        address,
+        allow_raw_hash: body.allow_raw_hash,
+        allow_signer_cache: body.allow_signer_cache,
+        total_shares: total_shares as i32,
+        threshold: threshold as i32,
+        commitments: commitments.to_hex(),
+        key_type: body.key_type,
+    };
+
+    // The key and its shares are stored together, the cloud share is deleted again when they
+    // can not be, rather than left behind without a key.
+    let Ok(txn) = app_data.get_db_connection().begin().await else {
+        delete_cloud_share(&app_data, &path).await;
+        return HttpResponse::InternalServerError().finish();
     };
 
-    let key = match create_key(key, app_data.get_db_connection()).await {
+    let key = match create_key(key, &txn).await {
         Ok(key) => key,
         Err(err) => {
+            delete_cloud_share(&app_data, &path).await;
             return HttpResponse::InternalServerError()
                 .body(format!("Error creating key: {}", err));
         }
     };
 
-    debug!("Share key: {}", shares[2].y.clone());
+    let mut user_shares = vec![];
+    let mut recovery_shares = vec![];
 
-    let share = match create_share(
-        CreateOrUpdateShare {
-            secret: shares[2].y.clone(),
-            key_id: key.id,
-            user_index: shares[2].x.clone(),
-            owner: SharesOwner::Admin,
-        },
-        app_data.get_db_connection(),
-    )
-    .await
-    {
-        Ok(share) => share,
-        Err(err) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Error creating share: {}", err));
+    for (index, user_share) in shares.iter().enumerate().skip(2) {
+        debug!("Share key: {}", user_share.y.clone());
+
+        let owner = if index < total_shares {
+            SharesOwner::Admin
+        } else {
+            SharesOwner::Recovery
+        };
+
+        let share = match create_share(
+            CreateOrUpdateShare {
+                secret: user_share.y.clone(),
+                key_id: key.id,
+                user_index: user_share.x.clone(),
+                owner: owner.clone(),
+            },
+            &txn,
+        )
+        .await
+        {
+            Ok(share) => share,
+            Err(err) => {
+                delete_cloud_share(&app_data, &path).await;
+                return HttpResponse::InternalServerError()
+                    .body(format!("Error creating share: {}", err));
+            }
+        };
+
+        let Ok(user_key) = ShareToken::encode_blocking(
+            key.id,
+            user_share,
+            body.format,
+            body.passphrase.as_deref(),
+        )
+        .await
+        else {
+            delete_cloud_share(&app_data, &path).await;
+            return HttpResponse::InternalServerError().finish();
+        };
+
+        let share = KeysShareResponse {
+            key: user_key,
+            id: share.id,
+            previous_id: None,
+        };
+
+        match owner {
+            SharesOwner::Recovery => recovery_shares.push(share),
+            _ => user_shares.push(share),
         }
-    };
+    }
 
-    let Ok(user_key) = hex::decode(&shares[2].y) else {
+    if txn.commit().await.is_err() {
+        delete_cloud_share(&app_data, &path).await;
         return HttpResponse::InternalServerError().finish();
-    };
+    }
 
     debug!("Generate key: {:?}", key);
 
+    let share_ids = user_shares.iter().map(|share| share.id).collect::<Vec<_>>();
+    let recovery_share_ids = recovery_shares
+        .iter()
+        .map(|share| share.id)
+        .collect::<Vec<_>>();
+
     let _ = create_log(
         CreateLog {
             key_id: key.id,
             action: "generate_key".to_string(),
             data: serde_json::json!({
-                "user_id": user.id
+                "user_id": user.id,
+                "allow_raw_hash": body.allow_raw_hash,
+                "allow_signer_cache": body.allow_signer_cache,
+                "key_type": body.key_type,
+                "shares": total_shares,
+                "threshold": threshold,
+                "share_ids": share_ids,
+                "recovery_share_ids": recovery_share_ids,
             }),
             message: None,
         },
@@ -134,21 +417,63 @@ pub async fn keys_generate_handler(req: HttpRequest, app_data: web::Data<AppData
     .await;
 
     HttpResponse::Ok().json(KeysGenerateResponse {
-        key: STANDARD.encode(user_key),
-        id: share.id,
+        key: user_shares[0].key.clone(),
+        id: user_shares[0].id,
+        key_id: key.id,
+        shares: user_shares,
+        recovery_shares,
     })
 }
 
-pub async fn keys_grant_handler(req: HttpRequest, app_data: web::Data<AppData>) -> HttpResponse {
+pub async fn keys_grant_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: Option<web::Json<KeysGrantRequest>>,
+) -> HttpResponse {
+    let body = body.map(|body| body.into_inner()).unwrap_or_default();
+
+    issue_share(&req, &app_data, body, false).await
+}
+
+/// Break-glass path for a lost admin share: the master key holder presents
+/// recovery shares issued at key generation (optionally mixed with remaining
+/// user shares) in the `x-secret-key` header and receives a new admin share.
+/// At least one recovery share is required, and the presented recovery shares are revoked so
+/// each one recovers a key only once.
+pub async fn keys_recover_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: Option<web::Json<KeysGrantRequest>>,
+) -> HttpResponse {
+    let body = body.map(|body| body.into_inner()).unwrap_or_default();
+
+    issue_share(&req, &app_data, body, true).await
+}
+
+async fn issue_share(
+    req: &HttpRequest,
+    app_data: &AppData,
+    body: KeysGrantRequest,
+    recovery: bool,
+) -> HttpResponse {
     let Some(Ok(master_key)) = req.headers().get(MASTER_KEY).map(|header| header.to_str()) else {
         return HttpResponse::Unauthorized().finish();
     };
 
-    let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
+    let header_key = req
+        .headers()
+        .get(SECRET_KEY)
+        .and_then(|header| header.to_str().ok());
+
+    let Some(secret_key) = body.secret_key.as_deref().or(header_key) else {
         return HttpResponse::Unauthorized().finish();
     };
 
-    let user = match get_user_by_secret(&master_key, app_data.get_db_connection()).await {
+    if let Err(response) = validate_passphrase(body.passphrase.as_deref()) {
+        return response;
+    }
+
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
         Ok(user) => user,
         Err(UserErrors::NotFound(_)) => {
             return HttpResponse::Unauthorized().finish();
@@ -158,68 +483,41 @@ pub async fn keys_grant_handler(req: HttpRequest, app_data: web::Data<AppData>)
         }
     };
 
-    let Ok(share) = STANDARD.decode(secret_key) else {
-        return HttpResponse::Unauthorized().finish();
-    };
-
-    let share_value = hex::encode(share);
-    let share = match get_share_by_secret(&share_value, app_data.get_db_connection()).await {
-        Ok(share) => share,
-        Err(ShareErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
-        Err(_) => {
-            return HttpResponse::Unauthorized().finish();
-        }
-    };
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
 
-    let key = match get_key_by_id(&share.key_id, app_data.get_db_connection()).await {
-        Ok(key) => key,
-        Err(KeyErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
-        Err(_) => {
-            return HttpResponse::Unauthorized().finish();
-        }
-    };
+    let (shares, key, share_ids) =
+        match restore_shares(secret_key, passphrase, app_data, recovery).await {
+            Ok(shares) => shares,
+            Err(err) => return restore_error_response(err),
+        };
 
     if key.user_id != user.id {
         return HttpResponse::Unauthorized().finish();
     }
 
-    let Ok(cloud_secret) = kv2::read::<ShareStore>(
-        app_data.get_vault_client().as_ref(),
-        "secret",
-        &key.cloud_key,
-    )
-    .await
-    else {
-        return HttpResponse::InternalServerError().finish();
-    };
-
-    let shares = vec![
-        Share {
-            x: BigUint::from_str_radix(&cloud_secret.x, 16).expect("Error parsing local index"),
-            y: BigUint::from_str_radix(&cloud_secret.y, 16).expect("Error parsing local key"),
-        },
-        Share {
-            x: BigUint::from_str_radix(&key.local_index, 16).expect("Error parsing local index"),
-            y: BigUint::from_str_radix(&key.local_key, 16).expect("Error parsing local key"),
-        },
-        Share {
-            x: BigUint::from_str_radix(&share.user_index, 16).expect("Error parsing user index"),
-            y: BigUint::from_str_radix(&share_value, 16).expect("Error parsing user key"),
-        },
-    ];
-
     let sss = Polynomial::new();
 
     let new_share = ShareStore::from(sss.add_share(&shares));
 
+    let Ok(txn) = app_data.get_db_connection().begin().await else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
     let share = match create_share(
         CreateOrUpdateShare {
             secret: new_share.y.to_string(),
             key_id: key.id,
             user_index: new_share.x.to_string(),
-            owner: SharesOwner::Guest,
+            owner: if recovery {
+                SharesOwner::Admin
+            } else {
+                SharesOwner::Guest
+            },
         },
-        app_data.get_db_connection(),
+        &txn,
     )
     .await
     {
@@ -230,17 +528,39 @@ pub async fn keys_grant_handler(req: HttpRequest, app_data: web::Data<AppData>)
         }
     };
 
-    let Ok(user_key) = hex::decode(&new_share.y).map(|k| STANDARD.encode(k)) else {
+    let consumed = match recovery {
+        true => match consume_recovery_shares(&share_ids, &txn).await {
+            Ok(Some(consumed)) if !consumed.is_empty() => consumed,
+            // Without a recovery share, recovery would turn any share into an admin share.
+            Ok(_) => return HttpResponse::Unauthorized().finish(),
+            Err(err) => {
+                return HttpResponse::InternalServerError()
+                    .body(format!("Error revoking recovery shares: {}", err));
+            }
+        },
+        false => vec![],
+    };
+
+    let Ok(user_key) =
+        ShareToken::encode_blocking(key.id, &new_share, body.format, body.passphrase.as_deref())
+            .await
+    else {
         return HttpResponse::InternalServerError().finish();
     };
 
+    if txn.commit().await.is_err() {
+        return HttpResponse::InternalServerError().finish();
+    }
+
     let _ = create_log(
         CreateLog {
             key_id: key.id,
-            action: "grant".to_string(),
+            action: if recovery { "recover" } else { "grant" }.to_string(),
             data: serde_json::json!({
                 "user_id": user.id,
                 "share_id": share.id,
+                "share_ids": share_ids,
+                "revoked_share_ids": consumed,
             }),
             message: None,
         },
@@ -249,8 +569,15 @@ pub async fn keys_grant_handler(req: HttpRequest, app_data: web::Data<AppData>)
     .await;
 
     HttpResponse::Ok().json(KeysGenerateResponse {
-        key: user_key,
+        key: user_key.clone(),
         id: share.id,
+        key_id: key.id,
+        shares: vec![KeysShareResponse {
+            key: user_key,
+            id: share.id,
+            previous_id: None,
+        }],
+        recovery_shares: vec![],
     })
 }
 
@@ -263,7 +590,7 @@ pub async fn keys_revoke_handler(
         return HttpResponse::Unauthorized().finish();
     };
 
-    let user = match get_user_by_secret(&master_key, app_data.get_db_connection()).await {
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
         Ok(user) => user,
         Err(UserErrors::NotFound(_)) => {
             return HttpResponse::Unauthorized().finish();
@@ -300,6 +627,10 @@ pub async fn keys_revoke_handler(
         return HttpResponse::InternalServerError().finish();
     }
 
+    if let Some(cache) = app_data.get_signer_cache() {
+        cache.invalidate_share(&share.id);
+    }
+
     let _ = create_log(
         CreateLog {
             key_id: key.id,
@@ -316,3 +647,839 @@ pub async fn keys_revoke_handler(
 
     HttpResponse::Ok().finish()
 }
+
+/// Proactive refresh: every share of the key is re-randomized with a zero-secret
+/// polynomial. Granted shares that were not presented, guest and recovery shares included,
+/// are rebuilt from the presented ones and re-issued with them; all of them are returned to
+/// the owner, with `previous_id` naming the share each one replaces.
+///
+/// The previous sharing is destroyed, so leaked shares become useless. `keep_previous`
+/// keeps it for [`keys_rollback_handler`] instead, which leaves the leaked shares usable
+/// again after a rollback; only ask for it when the refresh is not a response to a leak.
+pub async fn keys_refresh_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: Option<web::Json<KeysRefreshRequest>>,
+) -> HttpResponse {
+    let body = body.map(|body| body.into_inner()).unwrap_or_default();
+
+    let Some(Ok(master_key)) = req.headers().get(MASTER_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    if let Err(response) = validate_passphrase(body.passphrase.as_deref()) {
+        return response;
+    }
+
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
+        Ok(user) => user,
+        Err(UserErrors::NotFound(_)) => {
+            return HttpResponse::Unauthorized().finish();
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Error getting user: {}", e));
+        }
+    };
+
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
+
+    let (shares, key, share_ids) =
+        match restore_shares(secret_key, passphrase, &app_data, false).await {
+            Ok(shares) => shares,
+            Err(err) => return restore_error_response(err),
+        };
+
+    if key.user_id != user.id {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let granted = match get_granted_shares_by_key_id(&key.id, app_data.get_db_connection()).await {
+        Ok(granted) => granted,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let mut owners = vec![];
+
+    for share_id in share_ids.iter() {
+        match granted.iter().find(|share| share.id == *share_id) {
+            Some(share) => owners.push((share.owner.clone(), Some(share.id))),
+            // Revoked since it was restored.
+            None => return HttpResponse::Unauthorized().finish(),
+        }
+    }
+
+    let sss = Polynomial::new();
+
+    let mut rebuilt = vec![];
+
+    for share in granted
+        .iter()
+        .filter(|share| !share_ids.contains(&share.id))
+    {
+        let Ok(x) = BigUint::from_str_radix(&share.user_index, 16) else {
+            return HttpResponse::InternalServerError().finish();
+        };
+
+        rebuilt.push(sss.share_at(&shares, x));
+        owners.push((share.owner.clone(), Some(share.id)));
+    }
+
+    let max_shares = app_data
+        .get_config()
+        .max_shares
+        .unwrap_or(DEFAULT_MAX_SHARES);
+
+    // Existing shares are always re-issued, only new recovery shares are limited.
+    if body.recovery_shares > 0 && 2 + owners.len() + body.recovery_shares > max_shares {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Shares must not exceed {max_shares}")
+        }));
+    }
+
+    let total_shares = 2 + owners
+        .iter()
+        .filter(|(owner, _)| *owner != SharesOwner::Recovery)
+        .count();
+
+    let shares = shares.into_iter().chain(rebuilt).collect::<Vec<_>>();
+
+    let (refreshed, commitments) = match key.commitments.as_ref().map(Commitments::from_json) {
+        Some(Some(commitments)) => {
+            let (refreshed, commitments) = sss.refresh_verifiable_shares(&shares, &commitments);
+            (refreshed, Some(commitments.to_hex()))
+        }
+        Some(None) => return HttpResponse::InternalServerError().finish(),
+        None => (sss.refresh_shares(&shares, key.threshold as usize), None),
+    };
+
+    let recovery = (0..body.recovery_shares)
+        .map(|_| sss.add_share(&refreshed))
+        .collect::<Vec<_>>();
+
+    let refreshed = refreshed
+        .iter()
+        .chain(recovery.iter())
+        .map(Into::into)
+        .collect::<Vec<ShareStore>>();
+
+    let previous_share_ids = owners.iter().filter_map(|(_, id)| *id).collect::<Vec<_>>();
+
+    let owners = owners
+        .into_iter()
+        .chain(recovery.iter().map(|_| (SharesOwner::Recovery, None)))
+        .collect::<Vec<_>>();
+
+    let local = match seal_local_share(&app_data, &key.id, &refreshed[1]).await {
+        Ok(local) => local,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error encrypting local share: {}", err));
+        }
+    };
+
+    let ReplacedShares {
+        shares: user_shares,
+        recovery_shares,
+        revoked,
+    } = match replace_shares(
+        &app_data,
+        &key,
+        &refreshed,
+        owners,
+        body.format,
+        body.passphrase.as_deref(),
+        body.keep_previous,
+        UpdateKeyShares {
+            local_key: local.local_key,
+            local_index: local.local_index,
+            local_dek: local.local_dek,
+            cloud_key: app_data.get_share_path().render(&key.user_id, &key.id),
+            cloud_version: None,
+            commitments,
+            total_shares: total_shares as i32,
+            threshold: key.threshold,
+        },
+    )
+    .await
+    {
+        Ok(replaced) => replaced,
+        Err(response) => return response,
+    };
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "refresh".to_string(),
+            data: serde_json::json!({
+                "user_id": user.id,
+                "revoked": revoked,
+                "presented_share_ids": share_ids,
+                "previous_share_ids": previous_share_ids,
+                "share_ids": user_shares.iter().map(|share| share.id).collect::<Vec<_>>(),
+                "recovery_share_ids": recovery_shares
+                    .iter()
+                    .map(|share| share.id)
+                    .collect::<Vec<_>>(),
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(KeysGenerateResponse {
+        key: user_shares[0].key.clone(),
+        id: user_shares[0].id,
+        key_id: key.id,
+        shares: user_shares,
+        recovery_shares,
+    })
+}
+
+/// Re-shares the key to a new `shares`/`threshold` without changing its secret or address.
+/// The new user shares are all issued to the owner; every previous share is revoked.
+///
+/// As with a refresh, the previous sharing is destroyed unless `keep_previous` is set.
+pub async fn keys_reshare_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: web::Json<KeysReshareRequest>,
+) -> HttpResponse {
+    let Some(Ok(master_key)) = req.headers().get(MASTER_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
+        Ok(user) => user,
+        Err(UserErrors::NotFound(_)) => {
+            return HttpResponse::Unauthorized().finish();
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Error getting user: {}", e));
+        }
+    };
+
+    if let Err(response) =
+        validate_share_counts(&app_data, body.shares, body.threshold, body.recovery_shares)
+    {
+        return response;
+    }
+
+    if let Err(response) = validate_passphrase(body.passphrase.as_deref()) {
+        return response;
+    }
+
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
+
+    let (shares, key, share_ids) =
+        match restore_shares(secret_key, passphrase, &app_data, false).await {
+            Ok(shares) => shares,
+            Err(err) => return restore_error_response(err),
+        };
+
+    if key.user_id != user.id {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let sss = Polynomial::new();
+
+    let Some((reshared, commitments)) =
+        sss.reshare(&shares, body.shares + body.recovery_shares, body.threshold)
+    else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let reshared = reshared.iter().map(Into::into).collect::<Vec<ShareStore>>();
+
+    let owners = (2..body.shares + body.recovery_shares)
+        .map(|index| {
+            if index < body.shares {
+                (SharesOwner::Admin, None)
+            } else {
+                (SharesOwner::Recovery, None)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let local = match seal_local_share(&app_data, &key.id, &reshared[1]).await {
+        Ok(local) => local,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error encrypting local share: {}", err));
+        }
+    };
+
+    let ReplacedShares {
+        shares: user_shares,
+        recovery_shares,
+        revoked,
+    } = match replace_shares(
+        &app_data,
+        &key,
+        &reshared,
+        owners,
+        body.format,
+        body.passphrase.as_deref(),
+        body.keep_previous,
+        UpdateKeyShares {
+            local_key: local.local_key,
+            local_index: local.local_index,
+            local_dek: local.local_dek,
+            cloud_key: app_data.get_share_path().render(&key.user_id, &key.id),
+            cloud_version: None,
+            commitments: Some(commitments.to_hex()),
+            total_shares: body.shares as i32,
+            threshold: body.threshold as i32,
+        },
+    )
+    .await
+    {
+        Ok(replaced) => replaced,
+        Err(response) => return response,
+    };
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "reshare".to_string(),
+            data: serde_json::json!({
+                "user_id": user.id,
+                "revoked": revoked,
+                "previous_shares": key.total_shares,
+                "previous_threshold": key.threshold,
+                "shares": body.shares,
+                "threshold": body.threshold,
+                "previous_share_ids": share_ids,
+                "share_ids": user_shares.iter().map(|share| share.id).collect::<Vec<_>>(),
+                "recovery_share_ids": recovery_shares
+                    .iter()
+                    .map(|share| share.id)
+                    .collect::<Vec<_>>(),
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(KeysGenerateResponse {
+        key: user_shares[0].key.clone(),
+        id: user_shares[0].id,
+        key_id: key.id,
+        shares: user_shares,
+        recovery_shares,
+    })
+}
+
+/// Returns a key to an earlier version of its cloud share, kept by refreshes and reshares
+/// with `keep_previous` on versioned share storages. The shares of that version are granted
+/// again and the current ones revoked; the current version is kept, so the rollback can
+/// itself be undone.
+///
+/// Rolling back revives every share of the earlier sharing, including any that leaked. Keys
+/// only keep sharings when `key_versions_retained` is set, at most that many per key.
+pub async fn keys_rollback_handler(
+    req: HttpRequest,
+    app_data: web::Data<AppData>,
+    body: web::Json<KeysRollbackRequest>,
+) -> HttpResponse {
+    let Some(Ok(master_key)) = req.headers().get(MASTER_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
+        Ok(user) => user,
+        Err(UserErrors::NotFound(_)) => {
+            return HttpResponse::Unauthorized().finish();
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Error getting user: {}", e));
+        }
+    };
+
+    let key = match get_key_by_id(&body.key_id, app_data.get_db_connection()).await {
+        Ok(key) => key,
+        Err(KeyErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    if key.user_id != user.id {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let storage = app_data.get_share_storage();
+
+    let retained = app_data.get_config().key_versions_retained.unwrap_or(0);
+
+    if retained == 0 {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Key versions are not retained"}));
+    }
+
+    let Some(current) = key.cloud_version.filter(|_| storage.versioned()) else {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Cloud share of the key is not versioned"}));
+    };
+
+    if current == body.version {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Version is already the current one"}));
+    }
+
+    let version = match get_key_version(&key.id, body.version, app_data.get_db_connection()).await {
+        Ok(version) => version,
+        Err(KeyVersionErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    // Versions can be pruned by the storage, e.g. by the `max_versions` of a KV2 mount.
+    let cloud = match storage
+        .get_version(&key.cloud_key, body.version as u64)
+        .await
+    {
+        Ok(cloud) => cloud,
+        Err(StorageError::NotFound(_)) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": "Cloud share version no longer exists"}));
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error reading cloud share: {}", err));
+        }
+    };
+
+    let commitments = match version.commitments.as_ref() {
+        Some(json) => match (
+            Commitments::from_json(json),
+            serde_json::from_value::<Vec<String>>(json.clone()),
+        ) {
+            (Some(commitments), Ok(hex)) => Some((commitments, hex)),
+            _ => return HttpResponse::InternalServerError().finish(),
+        },
+        None => None,
+    };
+
+    let local = match open_local_share(
+        &app_data,
+        &KeyModel {
+            local_key: version.local_key.clone(),
+            local_index: version.local_index.clone(),
+            local_dek: version.local_dek.clone(),
+            ..key.clone()
+        },
+    )
+    .await
+    {
+        Ok(local) => local,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error opening local share: {}", err));
+        }
+    };
+
+    if let Some((commitments, _)) = &commitments {
+        let verified = |share: &ShareStore| {
+            parse_share(&share.x, &share.y).is_some_and(|share| commitments.verify(&share))
+        };
+
+        if !verified(&cloud) {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Cloud share does not match the key version"}));
+        }
+
+        if !verified(&local) {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Local share does not match the key version"}));
+        }
+    }
+
+    let share_ids = match serde_json::from_value::<Vec<Uuid>>(version.share_ids.clone()) {
+        Ok(share_ids) => share_ids,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let Ok(txn) = app_data.get_db_connection().begin().await else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let revoked = match revoke_shares_by_key_id(&key.id, &txn).await {
+        Ok(revoked) => revoked,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error revoking shares: {}", err));
+        }
+    };
+
+    if let Err(err) = save_key_version(&key, current, &revoked, &txn).await {
+        return HttpResponse::InternalServerError()
+            .body(format!("Error saving key version: {}", err));
+    }
+
+    if let Err(err) = delete_key_version(&key.id, body.version, &txn).await {
+        return HttpResponse::InternalServerError()
+            .body(format!("Error deleting key version: {}", err));
+    }
+
+    let pruned = match prune_key_versions(&key.id, retained, &txn).await {
+        Ok(pruned) => pruned,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Error pruning key versions: {}", err));
+        }
+    };
+
+    if let Err(err) = grant_shares_by_ids(&share_ids, &txn).await {
+        return HttpResponse::InternalServerError().body(format!("Error granting shares: {}", err));
+    }
+
+    let update = UpdateKeyShares {
+        local_key: version.local_key,
+        local_index: version.local_index,
+        local_dek: version.local_dek,
+        cloud_key: key.cloud_key.clone(),
+        cloud_version: Some(body.version),
+        commitments: commitments.map(|(_, hex)| hex),
+        total_shares: version.total_shares,
+        threshold: version.threshold,
+    };
+
+    if let Err(err) = update_key_shares(&key.id, update, &txn).await {
+        return HttpResponse::InternalServerError().body(format!("Error updating key: {}", err));
+    }
+
+    if let Err(err) = txn.commit().await {
+        return HttpResponse::InternalServerError()
+            .body(format!("Error rolling back key: {}", err));
+    }
+
+    if let Some(cache) = app_data.get_signer_cache() {
+        cache.invalidate_key(&key.id);
+    }
+
+    destroy_cloud_versions(&app_data, &key, &pruned).await;
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "rollback".to_string(),
+            data: serde_json::json!({
+                "user_id": user.id,
+                "previous_version": current,
+                "version": body.version,
+                "share_ids": share_ids,
+                "revoked_share_ids": revoked,
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(KeysRollbackResponse {
+        key_id: key.id,
+        version: body.version,
+        share_ids,
+        revoked_share_ids: revoked,
+    })
+}
+
+struct ReplacedShares {
+    shares: Vec<KeysShareResponse>,
+    recovery_shares: Vec<KeysShareResponse>,
+    revoked: u64,
+}
+
+/// Stores a new sharing of `key`: `shares[0]` goes to Vault under `update.cloud_key`,
+/// `shares[1]` to the key row and the rest to the shares table with the given `owners`,
+/// each paired with the share it replaces, if any.
+/// Every previous share is revoked in the same transaction.
+///
+/// On versioned storages the cloud share is instead rotated to a new version at the key's
+/// current path. Earlier versions are destroyed, unless `keep_previous` keeps the previous
+/// sharing in `key_versions` for a rollback, within `key_versions_retained`.
+#[allow(clippy::too_many_arguments)]
+async fn replace_shares(
+    app_data: &AppData,
+    key: &KeyModel,
+    shares: &[ShareStore],
+    owners: Vec<(SharesOwner, Option<Uuid>)>,
+    format: ShareFormat,
+    passphrase: Option<&str>,
+    keep_previous: bool,
+    mut update: UpdateKeyShares,
+) -> Result<ReplacedShares, HttpResponse> {
+    let storage = app_data.get_share_storage();
+
+    // Keys stored before their storage kept versions move to a new path, as before.
+    let rotate = key.cloud_version.filter(|_| storage.versioned());
+
+    let retained = app_data.get_config().key_versions_retained.unwrap_or(0);
+
+    if keep_previous && retained == 0 {
+        return Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Key versions are not retained"})));
+    }
+
+    if keep_previous && rotate.is_none() {
+        return Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Cloud share of the key is not versioned"})));
+    }
+
+    let stored = match rotate {
+        Some(_) => {
+            update.cloud_key = key.cloud_key.clone();
+            storage.update(&key.cloud_key, &shares[0]).await.map(Some)
+        }
+        None => storage.set(&update.cloud_key, &shares[0]).await,
+    };
+
+    update.cloud_version = match stored {
+        Ok(version) => version.map(|version| version as i64),
+        Err(err) => return Err(set_share_error_response(err)),
+    };
+
+    let update_version = update.cloud_version;
+
+    let Ok(txn) = app_data.get_db_connection().begin().await else {
+        return Err(HttpResponse::InternalServerError().finish());
+    };
+
+    if let Err(err) = update_key_shares(&key.id, update, &txn).await {
+        return Err(
+            HttpResponse::InternalServerError().body(format!("Error updating key: {}", err))
+        );
+    }
+
+    let revoked = match revoke_shares_by_key_id(&key.id, &txn).await {
+        Ok(revoked) => revoked,
+        Err(err) => {
+            return Err(
+                HttpResponse::InternalServerError().body(format!("Error revoking shares: {}", err))
+            );
+        }
+    };
+
+    let pruned = match rotate {
+        Some(version) if keep_previous => {
+            if let Err(err) = save_key_version(key, version, &revoked, &txn).await {
+                return Err(HttpResponse::InternalServerError()
+                    .body(format!("Error saving key version: {}", err)));
+            }
+
+            prune_key_versions(&key.id, retained, &txn).await
+        }
+        // Every earlier version, rollbacks can leave newer ones than the current behind.
+        Some(_) => delete_key_versions(&key.id, &txn)
+            .await
+            .map(|_| (1..update_version.unwrap_or_default()).collect()),
+        None => Ok(vec![]),
+    };
+
+    let pruned = match pruned {
+        Ok(pruned) => pruned,
+        Err(err) => {
+            return Err(HttpResponse::InternalServerError()
+                .body(format!("Error pruning key versions: {}", err)));
+        }
+    };
+
+    let mut user_shares = vec![];
+    let mut recovery_shares = vec![];
+
+    for (new_share, (owner, previous_id)) in shares.iter().skip(2).zip(owners) {
+        let share = match create_share(
+            CreateOrUpdateShare {
+                secret: new_share.y.clone(),
+                key_id: key.id,
+                user_index: new_share.x.clone(),
+                owner: owner.clone(),
+            },
+            &txn,
+        )
+        .await
+        {
+            Ok(share) => share,
+            Err(err) => {
+                return Err(HttpResponse::InternalServerError()
+                    .body(format!("Error creating share: {}", err)));
+            }
+        };
+
+        let Ok(user_key) = ShareToken::encode_blocking(key.id, new_share, format, passphrase).await
+        else {
+            return Err(HttpResponse::InternalServerError().finish());
+        };
+
+        let share = KeysShareResponse {
+            key: user_key,
+            id: share.id,
+            previous_id,
+        };
+
+        match owner {
+            SharesOwner::Recovery => recovery_shares.push(share),
+            _ => user_shares.push(share),
+        }
+    }
+
+    if let Err(err) = txn.commit().await {
+        return Err(
+            HttpResponse::InternalServerError().body(format!("Error replacing shares: {}", err))
+        );
+    }
+
+    if let Some(cache) = app_data.get_signer_cache() {
+        cache.invalidate_key(&key.id);
+    }
+
+    match rotate {
+        Some(_) => destroy_cloud_versions(app_data, key, &pruned).await,
+        None => {
+            if let Err(err) = storage.delete(&key.cloud_key).await {
+                warn!("Error deleting previous cloud share of {}: {}", key.id, err);
+            }
+        }
+    }
+
+    Ok(ReplacedShares {
+        shares: user_shares,
+        recovery_shares,
+        revoked: revoked.len() as u64,
+    })
+}
+
+/// Deletes the cloud share of a key that could not be stored.
+async fn delete_cloud_share(app_data: &AppData, path: &str) {
+    if let Err(err) = app_data.get_share_storage().delete(path).await {
+        warn!("Error deleting cloud share {}: {}", path, err);
+    }
+}
+
+/// Destroys cloud share versions no longer kept in `key_versions`. Failures only leave the
+/// versions behind in storage, they are not referenced anymore.
+async fn destroy_cloud_versions(app_data: &AppData, key: &KeyModel, versions: &[i64]) {
+    if versions.is_empty() {
+        return;
+    }
+
+    let versions = versions
+        .iter()
+        .map(|version| *version as u64)
+        .collect::<Vec<_>>();
+
+    if let Err(err) = app_data
+        .get_share_storage()
+        .destroy_versions(&key.cloud_key, &versions)
+        .await
+    {
+        warn!(
+            "Error destroying cloud share versions of {}: {}",
+            key.id, err
+        );
+    }
+}
+
+fn parse_share(x: &str, y: &str) -> Option<Share> {
+    Some(Share {
+        x: BigUint::from_str_radix(x, 16).ok()?,
+        y: BigUint::from_str_radix(y, 16).ok()?,
+    })
+}
+
+/// Checks a single user share, as well as the cloud and local shares of its key,
+/// against the Feldman commitments stored at key generation.
+pub async fn keys_check_handler(req: HttpRequest, app_data: web::Data<AppData>) -> HttpResponse {
+    let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
+
+    let token = match ShareToken::parse_blocking(secret_key, passphrase).await {
+        Ok(token) => token,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}));
+        }
+    };
+
+    let secret = token.secret();
+
+    let share = match get_share_by_secret(&secret, app_data.get_db_connection()).await {
+        Ok(share) => share,
+        Err(ShareErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    if !matches!(share.status, SharesStatus::Granted) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let key = match get_key_by_id(&share.key_id, app_data.get_db_connection()).await {
+        Ok(key) => key,
+        Err(KeyErrors::NotFound(_)) => return HttpResponse::NotFound().finish(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let Some(commitments) = key.commitments.as_ref().and_then(Commitments::from_json) else {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Key has no share commitments"}));
+    };
+
+    let verify = |share: Option<Share>| share.is_some_and(|share| commitments.verify(&share));
+
+    let cloud = match read_cloud_share(app_data.get_share_storage().as_ref(), &key).await {
+        Ok(cloud) => verify(parse_share(&cloud.x, &cloud.y)),
+        Err(err) => {
+            warn!("Error reading cloud share of {}: {}", key.id, err);
+            false
+        }
+    };
+
+    let token_matches = token.key_id().is_none_or(|key_id| key_id == key.id)
+        && token.x().is_none_or(|x| {
+            BigUint::from_str_radix(&share.user_index, 16).is_ok_and(|index| index == *x)
+        });
+
+    let share_valid = token_matches && verify(parse_share(&share.user_index, &secret));
+    let local = match open_local_share(&app_data, &key).await {
+        Ok(local) => verify(parse_share(&local.x, &local.y)),
+        Err(err) => {
+            warn!("Error opening local share of {}: {}", key.id, err);
+            false
+        }
+    };
+    let address = match key.key_type {
+        KeyType::Ecdsa => commitments
+            .address()
+            .is_some_and(|address| address.to_string() == key.address),
+        KeyType::Schnorr => commitments
+            .x_only_public_key()
+            .is_some_and(|public_key| hex::encode(public_key) == key.address),
+    };
+
+    HttpResponse::Ok().json(KeysCheckResponse {
+        key_id: key.id,
+        valid: share_valid && local && cloud && address,
+        share: share_valid,
+        local,
+        cloud,
+        address,
+    })
+}
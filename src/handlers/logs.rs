@@ -16,7 +16,7 @@ pub async fn get_logs_handler(
         return HttpResponse::Unauthorized().finish();
     };
 
-    let user = match get_user_by_secret(&master_key, app_data.get_db_connection()).await {
+    let user = match get_user_by_secret(master_key, app_data.get_db_connection()).await {
         Ok(user) => user,
         Err(UserErrors::NotFound(_)) => {
             return HttpResponse::Unauthorized().finish();
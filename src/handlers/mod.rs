@@ -1,23 +1,55 @@
 use actix_web::web;
 
-pub use keys::{KeysGenerateResponse, KeysRevokeRequest};
-pub use sign::{SignMessageRequest, SignMessageResponse};
+pub use crate::helpers::share_token::ShareFormat;
+pub use crate::models::keys::KeyType;
+pub use keys::{
+    KeysCheckResponse, KeysGenerateRequest, KeysGenerateResponse, KeysGrantRequest,
+    KeysRefreshRequest, KeysReshareRequest, KeysRevokeRequest, KeysRollbackRequest,
+    KeysRollbackResponse, KeysShareResponse,
+};
+pub use schnorr::{SignSchnorrRequest, SignSchnorrResponse};
+pub use sign::{
+    EncodedMessage, MessageEncoding, SignBatchItem, SignBatchRequest, SignBatchResponse,
+    SignHashRequest, SignHashResponse, SignMessageRequest, SignMessageResponse,
+    SignTransactionRequest, SignTransactionResponse, SignTypedDataRequest,
+};
 pub use users::CreateUserResponse;
+pub use verify::{VerifyPayload, VerifyRequest, VerifyResponse};
 
 mod healthcheck;
 mod keys;
 mod logs;
+mod schnorr;
 mod sign;
 mod users;
+mod verify;
 
 pub fn handlers(conf: &mut web::ServiceConfig) {
     let scope = web::scope("/api")
         .service(web::resource("/users").route(web::post().to(users::users_create_handler)))
         .service(web::resource("/logs/{id}").route(web::get().to(logs::get_logs_handler)))
         .service(web::resource("/keys/generate").route(web::post().to(keys::keys_generate_handler)))
+        .service(web::resource("/keys/check").route(web::post().to(keys::keys_check_handler)))
         .service(web::resource("/keys/grant").route(web::post().to(keys::keys_grant_handler)))
+        .service(web::resource("/keys/recover").route(web::post().to(keys::keys_recover_handler)))
+        .service(web::resource("/keys/refresh").route(web::post().to(keys::keys_refresh_handler)))
+        .service(web::resource("/keys/reshare").route(web::post().to(keys::keys_reshare_handler)))
         .service(web::resource("/keys/revoke").route(web::post().to(keys::keys_revoke_handler)))
-        .service(web::resource("/sign_message").route(web::post().to(sign::sign_message_handler)));
+        .service(web::resource("/keys/rollback").route(web::post().to(keys::keys_rollback_handler)))
+        .service(web::resource("/sign_message").route(web::post().to(sign::sign_message_handler)))
+        .service(
+            web::resource("/sign_typed_data").route(web::post().to(sign::sign_typed_data_handler)),
+        )
+        .service(
+            web::resource("/sign_transaction")
+                .route(web::post().to(sign::sign_transaction_handler)),
+        )
+        .service(web::resource("/sign_hash").route(web::post().to(sign::sign_hash_handler)))
+        .service(web::resource("/sign_batch").route(web::post().to(sign::sign_batch_handler)))
+        .service(
+            web::resource("/sign_schnorr").route(web::post().to(schnorr::sign_schnorr_handler)),
+        )
+        .service(web::resource("/verify").route(web::post().to(verify::verify_handler)));
 
     conf.service(scope);
     conf.service(web::resource("/").route(web::get().to(healthcheck::healthcheck_handler)));
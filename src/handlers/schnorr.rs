@@ -0,0 +1,91 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use alloy::primitives::B256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::constants::{PASSPHRASE, SECRET_KEY};
+use crate::helpers::restore_shares::restore_shares;
+use crate::models::keys::KeyType;
+use crate::queries::logs::{create_log, CreateLog};
+use crate::services::frost::{FrostError, FrostSigner};
+use crate::AppData;
+
+/// BIP-340 signs the 32 byte message as is, e.g. a Taproot sighash or a Nostr event id.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignSchnorrRequest {
+    pub message: B256,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignSchnorrResponse {
+    pub signature: String,
+    pub public_key: String,
+}
+
+pub async fn sign_schnorr_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignSchnorrRequest>,
+) -> HttpResponse {
+    let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
+
+    let (shares, key, share_ids) =
+        match restore_shares(secret_key, passphrase, &app_data, false).await {
+            Ok(shares) => shares,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+            }
+        };
+
+    if key.key_type != KeyType::Schnorr {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "Key does not support Schnorr signing"}));
+    }
+
+    let Ok(public_key) = hex::decode(&key.address) else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let signer = match FrostSigner::new(&shares, &public_key) {
+        Ok(signer) => signer,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+        }
+    };
+
+    // A share that passed restoration but does not belong to the key yields a signature
+    // that does not verify, like a corrupted share on the ECDSA endpoints.
+    let signature = match signer.sign(&body.message) {
+        Ok(signature) => signature,
+        Err(e @ FrostError::InvalidSignature) => {
+            return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+        }
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "sign_schnorr".to_string(),
+            data: json!({
+                "share_ids": share_ids,
+                "message": body.message,
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(SignSchnorrResponse {
+        signature: hex::encode(signature),
+        public_key: key.address,
+    })
+}
@@ -1,18 +1,107 @@
 use actix_web::{web, HttpRequest, HttpResponse};
+use alloy::consensus::{TxEip1559, TxEip2930, TxLegacy, TypedTransaction};
+use alloy::dyn_abi::TypedData;
+use alloy::eips::eip2718::Encodable2718;
+use alloy::eips::eip2930::AccessList;
+use alloy::primitives::{
+    eip191_hash_message, keccak256, Address, Bytes, ChainId, TxKind, B256, U256,
+};
 use alloy::signers::local::PrivateKeySigner;
-use alloy::signers::Signer;
+use alloy::signers::SignerSync;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sea_orm::TransactionTrait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tracing::warn;
+use uuid::Uuid;
+use zeroize::Zeroizing;
 
-use crate::constants::SECRET_KEY;
-use crate::helpers::restore_shares::restore_shares;
+use crate::constants::{MAX_BATCH_SIZE, PASSPHRASE, SECRET_KEY};
+use crate::helpers::restore_shares::{find_user_shares, restore_user_shares, UserShares};
+use crate::helpers::signer::KeySigner;
+use crate::helpers::transaction::sign_transaction;
+use crate::models::keys::{KeyType, Model};
 use crate::queries::logs::{create_log, CreateLog};
+use crate::services::additive::AdditiveSigner;
 use crate::services::polynomial::Polynomial;
-use crate::AppData;
+use crate::services::signer_cache::SignerCache;
+use crate::{AppData, SigningMode};
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    Hex,
+    Base64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
 pub struct SignMessageRequest {
     pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignTypedDataRequest {
+    #[serde(flatten)]
+    pub typed_data: TypedData,
+}
+
+/// A message inside a batch or a verification, either a plain UTF-8 string or an object with
+/// the message and its encoding.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(from = "EncodedMessageRepr")]
+pub struct EncodedMessage {
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EncodedMessageRepr {
+    Utf8(String),
+    Encoded {
+        message: String,
+        #[serde(default)]
+        encoding: MessageEncoding,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SignBatchItem {
+    Message(EncodedMessage),
+    TypedData(Box<TypedData>),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignBatchRequest {
+    pub items: Vec<SignBatchItem>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignHashRequest {
+    pub hash: B256,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct SignTransactionRequest {
+    pub chain_id: ChainId,
+    pub nonce: u64,
+    pub gas_limit: u128,
+    pub gas_price: Option<u128>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub to: Option<Address>,
+    #[serde(default)]
+    pub value: U256,
+    #[serde(default)]
+    pub data: Bytes,
+    pub access_list: Option<AccessList>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -20,40 +109,285 @@ pub struct SignMessageResponse {
     pub signature: String,
 }
 
-pub async fn sign_message_handler(
-    app_data: web::Data<AppData>,
-    req: HttpRequest,
-    body: web::Json<SignMessageRequest>,
-) -> HttpResponse {
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignBatchResponse {
+    pub signatures: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignHashResponse {
+    pub signature: String,
+    pub r: String,
+    pub s: String,
+    pub v: u8,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignTransactionResponse {
+    pub transaction: String,
+    pub hash: String,
+}
+
+impl MessageEncoding {
+    fn decode(&self, message: &str) -> Result<Vec<u8>, String> {
+        match self {
+            MessageEncoding::Utf8 => Ok(message.as_bytes().to_vec()),
+            MessageEncoding::Hex => hex::decode(message.strip_prefix("0x").unwrap_or(message))
+                .map_err(|e| e.to_string()),
+            MessageEncoding::Base64 => STANDARD.decode(message).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl From<EncodedMessageRepr> for EncodedMessage {
+    fn from(repr: EncodedMessageRepr) -> Self {
+        match repr {
+            EncodedMessageRepr::Utf8(message) => EncodedMessage {
+                message,
+                encoding: MessageEncoding::Utf8,
+            },
+            EncodedMessageRepr::Encoded { message, encoding } => {
+                EncodedMessage { message, encoding }
+            }
+        }
+    }
+}
+
+impl From<&str> for EncodedMessage {
+    fn from(message: &str) -> Self {
+        EncodedMessage {
+            message: message.to_string(),
+            encoding: MessageEncoding::Utf8,
+        }
+    }
+}
+
+impl EncodedMessage {
+    /// EIP-191 hash of the decoded message.
+    pub(crate) fn signing_hash(&self) -> Result<B256, String> {
+        Ok(eip191_hash_message(self.encoding.decode(&self.message)?))
+    }
+}
+
+impl TryFrom<&SignTransactionRequest> for TypedTransaction {
+    type Error = &'static str;
+
+    fn try_from(value: &SignTransactionRequest) -> Result<Self, Self::Error> {
+        let to = value.to.map(TxKind::Call).unwrap_or(TxKind::Create);
+
+        match (
+            value.gas_price,
+            value.max_fee_per_gas,
+            value.max_priority_fee_per_gas,
+        ) {
+            (None, Some(max_fee_per_gas), max_priority_fee_per_gas) => {
+                Ok(TypedTransaction::Eip1559(TxEip1559 {
+                    chain_id: value.chain_id,
+                    nonce: value.nonce,
+                    gas_limit: value.gas_limit,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas: max_priority_fee_per_gas.unwrap_or_default(),
+                    to,
+                    value: value.value,
+                    access_list: value.access_list.clone().unwrap_or_default(),
+                    input: value.data.clone(),
+                }))
+            }
+            (Some(gas_price), None, None) => match &value.access_list {
+                Some(access_list) => Ok(TypedTransaction::Eip2930(TxEip2930 {
+                    chain_id: value.chain_id,
+                    nonce: value.nonce,
+                    gas_price,
+                    gas_limit: value.gas_limit,
+                    to,
+                    value: value.value,
+                    access_list: access_list.clone(),
+                    input: value.data.clone(),
+                })),
+                None => Ok(TypedTransaction::Legacy(TxLegacy {
+                    chain_id: Some(value.chain_id),
+                    nonce: value.nonce,
+                    gas_price,
+                    gas_limit: value.gas_limit,
+                    to,
+                    value: value.value,
+                    input: value.data.clone(),
+                })),
+            },
+            (None, None, _) => Err("Either gas_price or max_fee_per_gas is required"),
+            _ => Err("gas_price can not be combined with max_fee_per_gas"),
+        }
+    }
+}
+
+/// A signer found in the cache, or the user shares and key to restore it from.
+enum PendingSigner {
+    Cached(KeySigner, Model, Vec<Uuid>),
+    Shares {
+        user_shares: UserShares,
+        credentials: [u8; 32],
+        epoch: Option<u64>,
+    },
+}
+
+impl PendingSigner {
+    fn key(&self) -> &Model {
+        match self {
+            PendingSigner::Cached(_, key, _) => key,
+            PendingSigner::Shares { user_shares, .. } => &user_shares.key,
+        }
+    }
+}
+
+async fn restore_signer(
+    req: &HttpRequest,
+    app_data: &AppData,
+) -> Result<(KeySigner, Model, Vec<Uuid>), HttpResponse> {
+    let pending = find_signer(req, app_data).await?;
+
+    finish_signer(pending, app_data).await
+}
+
+/// Resolves the key of the request, from the signer cache or the user shares, without
+/// reading the cloud or local share.
+async fn find_signer(req: &HttpRequest, app_data: &AppData) -> Result<PendingSigner, HttpResponse> {
     let Some(Ok(secret_key)) = req.headers().get(SECRET_KEY).map(|header| header.to_str()) else {
-        return HttpResponse::Unauthorized().finish();
+        return Err(HttpResponse::Unauthorized().finish());
+    };
+
+    let passphrase = req
+        .headers()
+        .get(PASSPHRASE)
+        .and_then(|header| header.to_str().ok());
+
+    let cache = app_data
+        .get_signer_cache()
+        .filter(|_| app_data.get_config().signing_mode == SigningMode::Reconstruct);
+    let credentials = SignerCache::credentials(secret_key, passphrase);
+
+    if let Some(cache) = &cache {
+        if let Some((signer, key, share_ids)) = cache.get(&credentials) {
+            return Ok(PendingSigner::Cached(
+                KeySigner::Local(signer),
+                key,
+                share_ids,
+            ));
+        }
+    }
+
+    let epoch = cache.as_ref().map(|cache| cache.epoch());
+
+    let user_shares = match find_user_shares(secret_key, passphrase, app_data, false).await {
+        Ok(user_shares) => user_shares,
+        Err(e) => {
+            return Err(HttpResponse::BadRequest().json(json!({"error": e.to_string()})));
+        }
+    };
+
+    if user_shares.key.key_type != KeyType::Ecdsa {
+        return Err(
+            HttpResponse::BadRequest().json(json!({"error": "Key does not support ECDSA signing"}))
+        );
+    }
+
+    Ok(PendingSigner::Shares {
+        user_shares,
+        credentials,
+        epoch,
+    })
+}
+
+async fn finish_signer(
+    pending: PendingSigner,
+    app_data: &AppData,
+) -> Result<(KeySigner, Model, Vec<Uuid>), HttpResponse> {
+    let (user_shares, credentials, epoch) = match pending {
+        PendingSigner::Cached(signer, key, share_ids) => return Ok((signer, key, share_ids)),
+        PendingSigner::Shares {
+            user_shares,
+            credentials,
+            epoch,
+        } => (user_shares, credentials, epoch),
     };
 
-    let (shares, key_id, share_id) = match restore_shares(secret_key, &app_data).await {
+    let (shares, key, share_ids) = match restore_user_shares(user_shares, app_data).await {
         Ok(shares) => shares,
         Err(e) => {
-            return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+            return Err(HttpResponse::BadRequest().json(json!({"error": e.to_string()})));
         }
     };
 
-    let sss = Polynomial::new();
+    let signer = match app_data.get_config().signing_mode {
+        SigningMode::Reconstruct => {
+            let sss = Polynomial::new();
 
-    let private_key = sss.reconstruct_secret(&shares);
+            let private_key = Zeroizing::new(
+                B256::left_padding_from(sss.reconstruct_secret(&shares).to_bytes_be().as_slice()).0,
+            );
 
-    let Ok(signer) = PrivateKeySigner::from_slice(private_key.to_bytes_be().as_slice()) else {
-        return HttpResponse::InternalServerError().finish();
+            let Ok(signer) = PrivateKeySigner::from_bytes(&B256::from(*private_key)) else {
+                return Err(HttpResponse::InternalServerError().finish());
+            };
+
+            if let (Some(cache), Some(epoch)) = (app_data.get_signer_cache(), epoch) {
+                if key.allow_signer_cache {
+                    cache.insert(
+                        credentials,
+                        epoch,
+                        private_key,
+                        key.clone(),
+                        share_ids.clone(),
+                    );
+                }
+            }
+
+            KeySigner::Local(signer)
+        }
+        SigningMode::Additive => {
+            let Ok(address) = key.address.parse::<Address>() else {
+                return Err(HttpResponse::InternalServerError().finish());
+            };
+
+            match AdditiveSigner::new(&shares, address) {
+                Ok(signer) => KeySigner::Additive(signer),
+                Err(e) => {
+                    return Err(HttpResponse::BadRequest().json(json!({"error": e.to_string()})));
+                }
+            }
+        }
     };
 
-    let Ok(signature) = signer.sign_message(body.message.as_bytes()).await else {
+    Ok((signer, key, share_ids))
+}
+
+pub async fn sign_message_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignMessageRequest>,
+) -> HttpResponse {
+    let message = match body.encoding.decode(&body.message) {
+        Ok(message) => message,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(json!({"error": e}));
+        }
+    };
+
+    let (signer, key, share_ids) = match restore_signer(&req, &app_data).await {
+        Ok(signer) => signer,
+        Err(response) => return response,
+    };
+
+    let Ok(signature) = signer.sign_message_sync(&message) else {
         return HttpResponse::InternalServerError().finish();
     };
 
     let _ = create_log(
         CreateLog {
-            key_id,
+            key_id: key.id,
             action: "sign_message".to_string(),
             data: json!({
-                "share_id": share_id,
+                "share_ids": share_ids,
+                "encoding": body.encoding,
             }),
             message: Some(body.message.clone()),
         },
@@ -65,3 +399,264 @@ pub async fn sign_message_handler(
         signature: hex::encode(signature.as_bytes()),
     })
 }
+
+pub async fn sign_typed_data_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignTypedDataRequest>,
+) -> HttpResponse {
+    let (signer, key, share_ids) = match restore_signer(&req, &app_data).await {
+        Ok(signer) => signer,
+        Err(response) => return response,
+    };
+
+    let typed_data = &body.typed_data;
+
+    let hash = match typed_data.eip712_signing_hash() {
+        Ok(hash) => hash,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+        }
+    };
+
+    let Ok(signature) = signer.sign_hash_sync(&hash) else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "sign_typed_data".to_string(),
+            data: json!({
+                "share_ids": share_ids,
+                "domain": typed_data.domain,
+                "primary_type": typed_data.primary_type,
+                "hash": hash,
+            }),
+            message: Some(typed_data.message.to_string()),
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(SignMessageResponse {
+        signature: hex::encode(signature.as_bytes()),
+    })
+}
+
+pub async fn sign_transaction_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignTransactionRequest>,
+) -> HttpResponse {
+    let tx = match TypedTransaction::try_from(&body.0) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(json!({"error": e}));
+        }
+    };
+
+    let (signer, key, share_ids) = match restore_signer(&req, &app_data).await {
+        Ok(signer) => signer,
+        Err(response) => return response,
+    };
+
+    let tx_type = tx.tx_type();
+
+    let envelope = match sign_transaction(&signer, tx) {
+        Ok(envelope) => envelope,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let encoded = envelope.encoded_2718();
+    let hash = keccak256(&encoded);
+
+    let _ = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "sign_transaction".to_string(),
+            data: json!({
+                "share_ids": share_ids,
+                "hash": hash,
+                "type": u8::from(tx_type),
+                "chain_id": body.chain_id,
+                "nonce": body.nonce,
+                "to": body.to,
+                "value": body.value,
+                "selector": body.data.get(0..4).map(hex::encode),
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    HttpResponse::Ok().json(SignTransactionResponse {
+        transaction: hex::encode(encoded),
+        hash: hex::encode(hash),
+    })
+}
+
+pub async fn sign_hash_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignHashRequest>,
+) -> HttpResponse {
+    let pending = match find_signer(&req, &app_data).await {
+        Ok(pending) => pending,
+        Err(response) => return response,
+    };
+
+    if !pending.key().allow_raw_hash {
+        let key_id = pending.key().id;
+        warn!("Refused raw hash signing for key {key_id}");
+
+        let log = create_log(
+            CreateLog {
+                key_id,
+                action: "sign_hash_refused".to_string(),
+                data: json!({
+                    "hash": body.hash,
+                }),
+                message: None,
+            },
+            app_data.get_db_connection(),
+        )
+        .await;
+
+        if log.is_err() {
+            return HttpResponse::InternalServerError().finish();
+        }
+
+        return HttpResponse::Forbidden()
+            .json(json!({"error": "Raw hash signing is disabled for this key"}));
+    }
+
+    let (signer, key, share_ids) = match finish_signer(pending, &app_data).await {
+        Ok(signer) => signer,
+        Err(response) => return response,
+    };
+
+    let Ok(signature) = signer.sign_hash_sync(&body.hash) else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let log = create_log(
+        CreateLog {
+            key_id: key.id,
+            action: "sign_hash".to_string(),
+            data: json!({
+                "share_ids": share_ids,
+                "hash": body.hash,
+            }),
+            message: None,
+        },
+        app_data.get_db_connection(),
+    )
+    .await;
+
+    if log.is_err() {
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Ok().json(SignHashResponse {
+        signature: hex::encode(signature.as_bytes()),
+        r: hex::encode(signature.r().to_be_bytes::<32>()),
+        s: hex::encode(signature.s().to_be_bytes::<32>()),
+        v: signature.v().y_parity_byte() + 27,
+    })
+}
+
+pub async fn sign_batch_handler(
+    app_data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Json<SignBatchRequest>,
+) -> HttpResponse {
+    if body.items.is_empty() || body.items.len() > MAX_BATCH_SIZE {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Batch must contain between 1 and {MAX_BATCH_SIZE} items")
+        }));
+    }
+
+    let mut hashes = vec![];
+
+    for (index, item) in body.items.iter().enumerate() {
+        let hash = match item {
+            SignBatchItem::Message(message) => match message.signing_hash() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    return HttpResponse::BadRequest().json(json!({"error": e, "index": index}));
+                }
+            },
+            SignBatchItem::TypedData(typed_data) => match typed_data.eip712_signing_hash() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    return HttpResponse::BadRequest()
+                        .json(json!({"error": e.to_string(), "index": index}));
+                }
+            },
+        };
+
+        hashes.push(hash);
+    }
+
+    let (signer, key, share_ids) = match restore_signer(&req, &app_data).await {
+        Ok(signer) => signer,
+        Err(response) => return response,
+    };
+
+    let mut signatures = vec![];
+
+    for hash in hashes.iter() {
+        let Ok(signature) = signer.sign_hash_sync(hash) else {
+            return HttpResponse::InternalServerError().finish();
+        };
+
+        signatures.push(hex::encode(signature.as_bytes()));
+    }
+
+    let batch_id = Uuid::new_v4();
+
+    let Ok(txn) = app_data.get_db_connection().begin().await else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    for (index, (item, hash)) in body.items.iter().zip(hashes.iter()).enumerate() {
+        let log = match item {
+            SignBatchItem::Message(message) => CreateLog {
+                key_id: key.id,
+                action: "sign_message".to_string(),
+                data: json!({
+                    "share_ids": share_ids,
+                    "batch_id": batch_id,
+                    "index": index,
+                    "encoding": message.encoding,
+                }),
+                message: Some(message.message.clone()),
+            },
+            SignBatchItem::TypedData(typed_data) => CreateLog {
+                key_id: key.id,
+                action: "sign_typed_data".to_string(),
+                data: json!({
+                    "share_ids": share_ids,
+                    "batch_id": batch_id,
+                    "index": index,
+                    "domain": typed_data.domain,
+                    "primary_type": typed_data.primary_type,
+                    "hash": hash,
+                }),
+                message: Some(typed_data.message.to_string()),
+            },
+        };
+
+        if create_log(log, &txn).await.is_err() {
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    if txn.commit().await.is_err() {
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Ok().json(SignBatchResponse { signatures })
+}
@@ -22,8 +22,6 @@ pub async fn users_create_handler(app_data: web::Data<AppData>) -> HttpResponse
     .await
     {
         Ok(_) => HttpResponse::Ok().json(CreateUserResponse { secret: code }),
-        Err(e) => {
-            return HttpResponse::InternalServerError().body(format!("Error creating user: {}", e));
-        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error creating user: {}", e)),
     }
 }
@@ -0,0 +1,79 @@
+use std::str::FromStr;
+
+use actix_web::{web, HttpResponse};
+use alloy::dyn_abi::TypedData;
+use alloy::primitives::{Address, Signature, B256};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::handlers::sign::EncodedMessage;
+use crate::queries::keys::{get_key_by_id, KeyErrors};
+use crate::AppData;
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyPayload {
+    Message(EncodedMessage),
+    TypedData(Box<TypedData>),
+    Hash(B256),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct VerifyRequest {
+    #[serde(flatten)]
+    pub payload: VerifyPayload,
+    pub signature: String,
+    pub key_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct VerifyResponse {
+    pub address: Address,
+    pub valid: Option<bool>,
+}
+
+impl VerifyPayload {
+    fn signing_hash(&self) -> Result<B256, String> {
+        match self {
+            VerifyPayload::Message(message) => message.signing_hash(),
+            VerifyPayload::TypedData(typed_data) => {
+                typed_data.eip712_signing_hash().map_err(|e| e.to_string())
+            }
+            VerifyPayload::Hash(hash) => Ok(*hash),
+        }
+    }
+}
+
+pub async fn verify_handler(
+    app_data: web::Data<AppData>,
+    body: web::Json<VerifyRequest>,
+) -> HttpResponse {
+    let hash = match body.payload.signing_hash() {
+        Ok(hash) => hash,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(json!({"error": e}));
+        }
+    };
+
+    let Ok(signature) = Signature::from_str(&body.signature) else {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid signature"}));
+    };
+
+    let Ok(address) = signature.recover_address_from_prehash(&hash) else {
+        return HttpResponse::BadRequest().json(json!({"error": "Unable to recover signer"}));
+    };
+
+    let valid = match body.key_id {
+        Some(key_id) => match get_key_by_id(&key_id, app_data.get_db_connection()).await {
+            Ok(key) => Some(Address::from_str(&key.address).is_ok_and(|a| a == address)),
+            // An unknown key answers like a mismatched one so the endpoint
+            // can't be used to probe which key ids exist.
+            Err(KeyErrors::NotFound(_)) => Some(false),
+            Err(_) => return HttpResponse::InternalServerError().finish(),
+        },
+        None => None,
+    };
+
+    HttpResponse::Ok().json(VerifyResponse { address, valid })
+}
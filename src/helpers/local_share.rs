@@ -0,0 +1,226 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::RngCore;
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::models::keys::Model as KeyModel;
+use crate::queries::key_versions::{
+    get_key_versions_with_plaintext_local_share, update_plaintext_key_version_local_share,
+    KeyVersionErrors,
+};
+use crate::queries::keys::{
+    get_keys_with_plaintext_local_share, has_encrypted_local_shares, update_plaintext_local_share,
+    KeyErrors, UpdateLocalShare,
+};
+use crate::services::kek::KekError;
+use crate::services::polynomial::ShareStore;
+use crate::AppData;
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum LocalShareError {
+    #[error("Local share is encrypted but no key-encryption key is configured")]
+    MissingKek,
+    #[error(transparent)]
+    Kek(#[from] KekError),
+    #[error("Local share could not be decrypted")]
+    Decrypt,
+    #[error("Local share was stored without its x coordinate, recreate the key")]
+    LegacyIndex,
+    #[error(transparent)]
+    Key(#[from] KeyErrors),
+    #[error(transparent)]
+    KeyVersion(#[from] KeyVersionErrors),
+}
+
+/// Key id and column name as associated data, so neither `local_key` and `local_index` nor
+/// the local shares of two keys can be swapped along with their data keys.
+fn associated_data(key_id: &Uuid, column: &str) -> Vec<u8> {
+    [key_id.as_bytes().as_slice(), column.as_bytes()].concat()
+}
+
+fn encrypt(cipher: &ChaCha20Poly1305, key_id: &Uuid, column: &str, value: &str) -> String {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: value.as_bytes(),
+                aad: &associated_data(key_id, column),
+            },
+        )
+        .expect("Encryption of a short value can not fail");
+
+    STANDARD.encode([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(
+    cipher: &ChaCha20Poly1305,
+    key_id: &Uuid,
+    column: &str,
+    value: &str,
+) -> Result<String, LocalShareError> {
+    let payload = STANDARD
+        .decode(value)
+        .map_err(|_| LocalShareError::Decrypt)?;
+
+    if payload.len() <= NONCE_LEN {
+        return Err(LocalShareError::Decrypt);
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &associated_data(key_id, column),
+            },
+        )
+        .map_err(|_| LocalShareError::Decrypt)?;
+
+    String::from_utf8(plaintext).map_err(|_| LocalShareError::Decrypt)
+}
+
+/// Encrypts the local share with a fresh data key wrapped by the configured key-encryption
+/// key. Without one the share is kept in plaintext, as before.
+pub async fn seal_local_share(
+    app_data: &AppData,
+    key_id: &Uuid,
+    share: &ShareStore,
+) -> Result<UpdateLocalShare, LocalShareError> {
+    let Some(kek) = app_data.get_kek() else {
+        return Ok(UpdateLocalShare {
+            local_key: share.y.clone(),
+            local_index: share.x.clone(),
+            local_dek: None,
+        });
+    };
+
+    let mut dek = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(dek.as_mut_slice());
+
+    let local_dek = kek.wrap(&dek).await?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(dek.as_slice()));
+
+    Ok(UpdateLocalShare {
+        local_key: encrypt(&cipher, key_id, "local_key", &share.y),
+        local_index: encrypt(&cipher, key_id, "local_index", &share.x),
+        local_dek: Some(local_dek),
+    })
+}
+
+pub async fn open_local_share(
+    app_data: &AppData,
+    key: &KeyModel,
+) -> Result<ShareStore, LocalShareError> {
+    let Some(local_dek) = &key.local_dek else {
+        return check_index(ShareStore {
+            x: key.local_index.clone(),
+            y: key.local_key.clone(),
+        });
+    };
+
+    let kek = app_data.get_kek().ok_or(LocalShareError::MissingKek)?;
+
+    let dek = kek.unwrap(local_dek).await?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(dek.as_slice()));
+
+    check_index(ShareStore {
+        x: decrypt(&cipher, &key.id, "local_index", &key.local_index)?,
+        y: decrypt(&cipher, &key.id, "local_key", &key.local_key)?,
+    })
+}
+
+/// Keys created before `local_index` held the x coordinate stored the y value in both columns.
+/// Their random x was never persisted, so they would restore a different private key than the
+/// one behind `keys.address`; refuse them instead of signing with it.
+fn check_index(share: ShareStore) -> Result<ShareStore, LocalShareError> {
+    if share.x == share.y {
+        return Err(LocalShareError::LegacyIndex);
+    }
+
+    Ok(share)
+}
+
+/// Encrypts local shares stored before a key-encryption key was configured, those of kept
+/// key versions included. Returns the number of encrypted local shares.
+///
+/// Once local shares are encrypted, a missing key-encryption key is a
+/// [`LocalShareError::MissingKek`] rather than a deploy that keeps storing new local shares
+/// in plaintext and can not open the encrypted ones.
+///
+/// Run at startup rather than as a migration, since migrations have no access to the
+/// key-encryption key. Only plaintext rows are updated, so it is safe to run on every start
+/// and from several replicas at once.
+pub async fn encrypt_local_shares(app_data: &AppData) -> Result<usize, LocalShareError> {
+    if app_data.get_kek().is_none() {
+        if has_encrypted_local_shares(app_data.get_db_connection()).await? {
+            return Err(LocalShareError::MissingKek);
+        }
+
+        warn!("No key-encryption key configured, local shares are stored in plaintext");
+
+        return Ok(0);
+    }
+
+    let keys = get_keys_with_plaintext_local_share(app_data.get_db_connection()).await?;
+    let mut encrypted = 0;
+
+    for key in keys {
+        let sealed = seal_local_share(
+            app_data,
+            &key.id,
+            &ShareStore {
+                x: key.local_index,
+                y: key.local_key,
+            },
+        )
+        .await?;
+
+        if update_plaintext_local_share(&key.id, sealed, app_data.get_db_connection()).await? {
+            encrypted += 1;
+        }
+    }
+
+    let versions =
+        get_key_versions_with_plaintext_local_share(app_data.get_db_connection()).await?;
+
+    for version in versions {
+        let sealed = seal_local_share(
+            app_data,
+            &version.key_id,
+            &ShareStore {
+                x: version.local_index,
+                y: version.local_key,
+            },
+        )
+        .await?;
+
+        if update_plaintext_key_version_local_share(
+            &version.key_id,
+            version.cloud_version,
+            sealed,
+            app_data.get_db_connection(),
+        )
+        .await?
+        {
+            encrypted += 1;
+        }
+    }
+
+    info!("Encrypted {encrypted} plaintext local shares");
+
+    Ok(encrypted)
+}
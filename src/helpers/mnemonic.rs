@@ -0,0 +1,226 @@
+use lazy_static::lazy_static;
+use thiserror::Error;
+
+/// Customization string of the RS1024 checksum. It differs from SLIP-39's `shamir`, so share
+/// words are never mistaken for (or accepted by) SLIP-39 wallets.
+const CUSTOMIZATION: &[u8] = b"kms";
+const CHECKSUM_WORDS: usize = 3;
+const RADIX_BITS: usize = 10;
+/// SLIP-39 words are uniquely identified by their first four letters.
+const PREFIX_LEN: usize = 4;
+
+const GEN: [u32; 10] = [
+    0x00e0_e040,
+    0x01c1_c080,
+    0x0383_8100,
+    0x0707_0200,
+    0x0e0e_0009,
+    0x1c0c_2412,
+    0x3808_6c24,
+    0x3090_fc48,
+    0x21b1_f890,
+    0x03f3_f120,
+];
+
+lazy_static! {
+    static ref WORDLIST: Vec<&'static str> = include_str!("slip39_wordlist.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .collect();
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MnemonicError {
+    #[error("Unknown word \"{0}\"")]
+    UnknownWord(String),
+    #[error("Mnemonic has an invalid number of words, it may be incomplete")]
+    Length,
+    #[error("Mnemonic checksum mismatch, a word may be misspelled or out of order")]
+    Checksum,
+}
+
+fn polymod(customization: &[u8], values: &[u32]) -> u32 {
+    customization
+        .iter()
+        .map(|byte| u32::from(*byte))
+        .chain(values.iter().copied())
+        .fold(1, |chk, value| {
+            let b = chk >> 20;
+            let chk = ((chk & 0xfffff) << 10) ^ value;
+
+            GEN.iter()
+                .enumerate()
+                .filter(|(i, _)| (b >> i) & 1 == 1)
+                .fold(chk, |chk, (_, generator)| chk ^ generator)
+        })
+}
+
+fn create_checksum(customization: &[u8], data: &[u32]) -> [u32; CHECKSUM_WORDS] {
+    let mut values = data.to_vec();
+    values.extend([0; CHECKSUM_WORDS]);
+
+    let polymod = polymod(customization, &values) ^ 1;
+
+    let mut checksum = [0; CHECKSUM_WORDS];
+    for (i, word) in checksum.iter_mut().enumerate() {
+        *word = (polymod >> (RADIX_BITS * (CHECKSUM_WORDS - 1 - i))) & 1023;
+    }
+    checksum
+}
+
+fn word_index(word: &str) -> Result<u32, MnemonicError> {
+    let word = word.to_lowercase();
+
+    let position = WORDLIST
+        .binary_search(&word.as_str())
+        .ok()
+        .or_else(|| {
+            // Allow the four letter abbreviations people tend to write down.
+            (word.len() == PREFIX_LEN)
+                .then(|| {
+                    WORDLIST
+                        .iter()
+                        .position(|candidate| candidate.starts_with(&word))
+                })
+                .flatten()
+        })
+        .ok_or(MnemonicError::UnknownWord(word))?;
+
+    Ok(position as u32)
+}
+
+/// Number of words, checksum included, that encode `length` bytes.
+pub fn word_count(length: usize) -> usize {
+    (length * 8).div_ceil(RADIX_BITS) + CHECKSUM_WORDS
+}
+
+/// Encodes bytes as words of the SLIP-39 wordlist followed by an RS1024 checksum. The data
+/// is left padded with zero bits to a multiple of ten bits.
+pub fn encode(data: &[u8]) -> String {
+    let words = (data.len() * 8).div_ceil(RADIX_BITS);
+    let padding = words * RADIX_BITS - data.len() * 8;
+
+    let mut values = Vec::with_capacity(words + CHECKSUM_WORDS);
+    let mut accumulator = 0u32;
+    let mut bits = padding;
+
+    for byte in data {
+        accumulator = (accumulator << 8) | u32::from(*byte);
+        bits += 8;
+
+        if bits >= RADIX_BITS {
+            bits -= RADIX_BITS;
+            values.push((accumulator >> bits) & 1023);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+
+    let checksum = create_checksum(CUSTOMIZATION, &values);
+    values.extend(checksum);
+
+    values
+        .iter()
+        .map(|value| WORDLIST[*value as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Decodes `length` bytes from a mnemonic created by [`encode`].
+pub fn decode(mnemonic: &str, length: usize) -> Result<Vec<u8>, MnemonicError> {
+    let values = mnemonic
+        .split_whitespace()
+        .map(word_index)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if values.len() != word_count(length) {
+        return Err(MnemonicError::Length);
+    }
+
+    let words = values.len() - CHECKSUM_WORDS;
+
+    if polymod(CUSTOMIZATION, &values) != 1 {
+        return Err(MnemonicError::Checksum);
+    }
+
+    let padding = words * RADIX_BITS - length * 8;
+
+    if values[0] >> (RADIX_BITS - padding) != 0 {
+        return Err(MnemonicError::Checksum);
+    }
+
+    let mut data = Vec::with_capacity(length);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+
+    for (i, value) in values[..words].iter().enumerate() {
+        let width = if i == 0 {
+            RADIX_BITS - padding
+        } else {
+            RADIX_BITS
+        };
+
+        accumulator = (accumulator << width) | value;
+        bits += width;
+
+        while bits >= 8 {
+            bits -= 8;
+            data.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::mnemonic::{decode, encode, polymod, word_index, MnemonicError};
+
+    #[test]
+    fn test_mnemonic() {
+        // First SLIP-39 test vector, checksummed with the `shamir` customization string.
+        let vector = "duckling enlarge academic academic agency result length solution fridge \
+                      kidney coal piece deal husband erode duke ajar critical decision keyboard";
+        let values = vector
+            .split_whitespace()
+            .map(word_index)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(polymod(b"shamir", &values), 1);
+
+        for length in [1, 16, 32, 81] {
+            let data = (0..length).map(|i| (i * 37 + 11) as u8).collect::<Vec<_>>();
+            let mnemonic = encode(&data);
+
+            assert_eq!(decode(&mnemonic, length).unwrap(), data);
+            assert_eq!(
+                decode(&mnemonic.to_uppercase(), length).unwrap(),
+                data,
+                "Words are case insensitive"
+            );
+        }
+
+        let data = (0..81).map(|i| (i * 101 + 7) as u8).collect::<Vec<_>>();
+        let mnemonic = encode(&data);
+        let mut words = mnemonic.split(' ').collect::<Vec<_>>();
+
+        let abbreviated = words
+            .iter()
+            .map(|word| &word[..word.len().min(4)])
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(decode(&abbreviated, 81).unwrap(), data);
+
+        words.swap(3, 4);
+        assert_eq!(decode(&words.join(" "), 81), Err(MnemonicError::Checksum));
+
+        words.pop();
+        assert_eq!(decode(&words.join(" "), 81), Err(MnemonicError::Length));
+
+        assert_eq!(
+            decode(&mnemonic.replacen(words[0], "bitcoin", 1), 81),
+            Err(MnemonicError::UnknownWord("bitcoin".to_string()))
+        );
+    }
+}
@@ -1,3 +1,9 @@
 pub mod generate_code;
 pub mod keccak256;
+pub mod local_share;
+pub mod mnemonic;
 pub mod restore_shares;
+pub mod share_path;
+pub mod share_token;
+pub mod signer;
+pub mod transaction;
@@ -1,34 +1,46 @@
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
 use num_bigint::BigUint;
 use num_traits::Num;
 use sea_orm::DbErr;
 use thiserror::Error;
 use tracing::debug;
 use uuid::Uuid;
-use vaultrs::error::ClientError;
-use vaultrs::kv2;
 
-use crate::models::shares::SharesStatus;
+use crate::helpers::local_share::{open_local_share, LocalShareError};
+use crate::helpers::share_token::{ShareToken, ShareTokenError};
+use crate::models::keys::Model as KeyModel;
+use crate::models::shares::{Model, SharesOwner, SharesStatus};
 use crate::queries::keys::{get_key_by_id, KeyErrors};
 use crate::queries::shares::{get_share_by_secret, ShareErrors};
-use crate::services::polynomial::{Share, ShareStore};
+use crate::services::polynomial::{Commitments, Share};
+use crate::services::storage::{read_cloud_share, StorageError};
 use crate::AppData;
 
 #[derive(Debug, Error)]
 pub enum RestoreSharesError {
-    #[error("Invalid secret key")]
-    DecodeError(#[from] base64::DecodeError),
+    #[error("Invalid secret key: {0}")]
+    DecodeError(#[from] ShareTokenError),
     #[error("Share {0} not found")]
     ShareNotFound(String),
     #[error("Database error: {0}")]
     DbErr(DbErr),
-    #[error("Vault error: {0}")]
-    Storage(ClientError),
+    #[error("Storage error: {0}")]
+    Storage(StorageError),
+    #[error("Local share error: {0}")]
+    LocalShare(#[from] LocalShareError),
     #[error("Error parsing BigInt: {0}")]
     BigInt(#[from] num_bigint::ParseBigIntError),
     #[error("Key revoked")]
     Revoked,
+    #[error("Recovery shares can only be used to recover a key")]
+    RecoveryShare,
+    #[error("Shares belong to different keys")]
+    KeyMismatch,
+    #[error("Share token does not match the stored share")]
+    TokenMismatch,
+    #[error("At least {0} user shares are required")]
+    NotEnoughShares(usize),
+    #[error("Share {0} does not match the key commitments")]
+    InvalidShare(String),
 }
 
 impl From<ShareErrors> for RestoreSharesError {
@@ -49,45 +61,143 @@ impl From<KeyErrors> for RestoreSharesError {
     }
 }
 
+/// User shares parsed from a secret key together with their key, before the cloud and local
+/// shares are read.
+pub struct UserShares {
+    pub key: KeyModel,
+    shares: Vec<(Model, String)>,
+}
+
 pub async fn restore_shares(
     secret_key: &str,
+    passphrase: Option<&str>,
     app_data: &AppData,
-) -> Result<(Vec<Share>, Uuid, Uuid), RestoreSharesError> {
-    let share = STANDARD
-        .decode(secret_key)
-        .map_err(RestoreSharesError::DecodeError)?;
-    let share_value = hex::encode(share.as_slice());
-    debug!("Restoring shares for secret key: {share_value}: {share:?}");
-    let share = get_share_by_secret(&share_value, app_data.get_db_connection()).await?;
-
-    if !matches!(share.status, SharesStatus::Granted) {
-        return Err(RestoreSharesError::Revoked);
+    allow_recovery: bool,
+) -> Result<(Vec<Share>, KeyModel, Vec<Uuid>), RestoreSharesError> {
+    let user_shares = find_user_shares(secret_key, passphrase, app_data, allow_recovery).await?;
+
+    restore_user_shares(user_shares, app_data).await
+}
+
+/// Looks up the user shares of a secret key and their key without touching the cloud or local
+/// share, so callers can check the key before restoring it.
+pub async fn find_user_shares(
+    secret_key: &str,
+    passphrase: Option<&str>,
+    app_data: &AppData,
+    allow_recovery: bool,
+) -> Result<UserShares, RestoreSharesError> {
+    let mut user_shares: Vec<(Model, String)> = vec![];
+
+    for secret in secret_key.split(',').map(str::trim) {
+        let token = ShareToken::parse_blocking(secret, passphrase).await?;
+
+        if let (Some(key_id), Some((first, _))) = (token.key_id(), user_shares.first()) {
+            if first.key_id != key_id {
+                return Err(RestoreSharesError::KeyMismatch);
+            }
+        }
+
+        let share_value = token.secret();
+        debug!("Restoring shares for secret key: {share_value}");
+        let share = get_share_by_secret(&share_value, app_data.get_db_connection()).await?;
+
+        if let ShareToken::V1 { key_id, x, .. } = &token {
+            if share.key_id != *key_id || BigUint::from_str_radix(&share.user_index, 16)? != *x {
+                return Err(RestoreSharesError::TokenMismatch);
+            }
+        }
+
+        if !matches!(share.status, SharesStatus::Granted) {
+            return Err(RestoreSharesError::Revoked);
+        }
+
+        if !allow_recovery && matches!(share.owner, SharesOwner::Recovery) {
+            return Err(RestoreSharesError::RecoveryShare);
+        }
+
+        if user_shares.iter().any(|(known, _)| known.id == share.id) {
+            continue;
+        }
+
+        if let Some((first, _)) = user_shares.first() {
+            if first.key_id != share.key_id {
+                return Err(RestoreSharesError::KeyMismatch);
+            }
+        }
+
+        user_shares.push((share, share_value));
     }
 
-    let key = get_key_by_id(&share.key_id, app_data.get_db_connection()).await?;
+    let Some((first, _)) = user_shares.first() else {
+        return Err(RestoreSharesError::ShareNotFound("Share".to_string()));
+    };
 
-    let cloud_secret = kv2::read::<ShareStore>(
-        app_data.get_vault_client().as_ref(),
-        "secret",
-        &key.cloud_key,
-    )
-    .await
-    .map_err(RestoreSharesError::Storage)?;
+    let key = get_key_by_id(&first.key_id, app_data.get_db_connection()).await?;
 
-    let shares = vec![
+    let required = (key.threshold as usize).saturating_sub(2).max(1);
+
+    if user_shares.len() < required {
+        return Err(RestoreSharesError::NotEnoughShares(required));
+    }
+
+    user_shares.truncate(required);
+
+    Ok(UserShares {
+        key,
+        shares: user_shares,
+    })
+}
+
+pub async fn restore_user_shares(
+    user_shares: UserShares,
+    app_data: &AppData,
+) -> Result<(Vec<Share>, KeyModel, Vec<Uuid>), RestoreSharesError> {
+    let UserShares {
+        key,
+        shares: user_shares,
+    } = user_shares;
+
+    let cloud_secret = read_cloud_share(app_data.get_share_storage().as_ref(), &key)
+        .await
+        .map_err(RestoreSharesError::Storage)?;
+
+    let local_secret = open_local_share(app_data, &key).await?;
+
+    let mut shares = vec![
         Share {
             x: BigUint::from_str_radix(&cloud_secret.x, 16)?,
             y: BigUint::from_str_radix(&cloud_secret.y, 16)?,
         },
         Share {
-            x: BigUint::from_str_radix(&key.local_index, 16)?,
-            y: BigUint::from_str_radix(&key.local_key, 16)?,
+            x: BigUint::from_str_radix(&local_secret.x, 16)?,
+            y: BigUint::from_str_radix(&local_secret.y, 16)?,
         },
-        Share {
+    ];
+
+    let mut share_ids = vec![];
+
+    for (share, share_value) in user_shares {
+        shares.push(Share {
             x: BigUint::from_str_radix(&share.user_index, 16)?,
             y: BigUint::from_str_radix(&share_value, 16)?,
-        },
-    ];
+        });
+        share_ids.push(share.id);
+    }
+
+    if let Some(commitments) = key.commitments.as_ref().and_then(Commitments::from_json) {
+        let names = ["cloud".to_string(), "local".to_string()]
+            .into_iter()
+            .chain(share_ids.iter().map(Uuid::to_string));
+
+        if let Some((_, name)) = shares
+            .iter()
+            .zip(names)
+            .find(|(share, _)| !commitments.verify(share))
+        {
+            return Err(RestoreSharesError::InvalidShare(name));
+        }
+    }
 
-    Ok((shares, share.key_id, share.id))
+    Ok((shares, key, share_ids))
 }
@@ -0,0 +1,118 @@
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::helpers::generate_code::generate_code;
+
+const PLACEHOLDERS: [&str; 4] = ["{env}", "{user_id}", "{key_id}", "{code}"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SharePathError {
+    #[error("Unknown placeholder in SHARE_PATH_TEMPLATE: {0}")]
+    Placeholder(String),
+    #[error("SHARE_PATH_TEMPLATE uses {{env}} but ENVIRONMENT is not set")]
+    MissingEnvironment,
+    #[error("Invalid SHARE_PATH_TEMPLATE: {0}")]
+    Template(String),
+}
+
+/// Renders the storage path of cloud shares from `share_path_template`.
+#[derive(Debug, Clone)]
+pub struct SharePath {
+    template: String,
+    env: String,
+}
+
+impl SharePath {
+    pub fn from_config(config: &Config) -> Result<Self, SharePathError> {
+        let template = config
+            .share_path_template
+            .as_deref()
+            .unwrap_or("{code}")
+            .trim_matches('/');
+
+        // Every sharing of a key gets its own path, the random code keeps them apart.
+        let template = match template.contains("{code}") {
+            true => template.to_string(),
+            false => format!("{template}/{{code}}"),
+        };
+
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| SharePathError::Template(template.clone()))?;
+            let placeholder = &rest[start..start + end + 1];
+
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(SharePathError::Placeholder(placeholder.to_string()));
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if template.split('/').any(|segment| segment.is_empty()) {
+            return Err(SharePathError::Template(template));
+        }
+
+        let env = match (template.contains("{env}"), &config.environment) {
+            (true, None) => return Err(SharePathError::MissingEnvironment),
+            (_, env) => env.clone().unwrap_or_default(),
+        };
+
+        Ok(SharePath { template, env })
+    }
+
+    pub fn render(&self, user_id: &Uuid, key_id: &Uuid) -> String {
+        self.template
+            .replace("{env}", &self.env)
+            .replace("{user_id}", &user_id.to_string())
+            .replace("{key_id}", &key_id.to_string())
+            .replace("{code}", &generate_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::config::Config;
+    use crate::helpers::share_path::{SharePath, SharePathError};
+
+    fn config(template: Option<&str>, environment: Option<&str>) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_url": "",
+            "share_path_template": template,
+            "environment": environment,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_share_path() {
+        let user_id = Uuid::new_v4();
+        let key_id = Uuid::new_v4();
+
+        let path = SharePath::from_config(&config(None, None)).unwrap();
+        assert_eq!(path.render(&user_id, &key_id).len(), 8);
+
+        let path = SharePath::from_config(&config(
+            Some("kms/{env}/{user_id}/{key_id}"),
+            Some("staging"),
+        ))
+        .unwrap();
+        let rendered = path.render(&user_id, &key_id);
+        assert!(rendered.starts_with(&format!("kms/staging/{user_id}/{key_id}/")));
+        assert_ne!(rendered, path.render(&user_id, &key_id));
+
+        assert_eq!(
+            SharePath::from_config(&config(Some("kms/{env}"), None)).unwrap_err(),
+            SharePathError::MissingEnvironment
+        );
+        assert_eq!(
+            SharePath::from_config(&config(Some("kms/{tenant}"), None)).unwrap_err(),
+            SharePathError::Placeholder("{tenant}".to_string())
+        );
+        assert!(SharePath::from_config(&config(Some("kms//{code}"), None)).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use alloy::primitives::keccak256;
+use argon2::Argon2;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use num_bigint::BigUint;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::helpers::mnemonic::{self, MnemonicError};
+use crate::services::polynomial::ShareStore;
+
+/// Prefix of versioned tokens. Legacy tokens are standard base64, which never contains `_`.
+const PREFIX: &str = "kms_";
+const VERSION: u8 = 1;
+/// Version of tokens whose body is encrypted with a passphrase derived key.
+const WRAPPED_VERSION: u8 = 2;
+const CHECKSUM_LEN: usize = 4;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// version + key id + x + y
+const BODY_LEN: usize = 1 + 16 + 32 + 32;
+// version + salt + nonce + encrypted body
+const WRAPPED_LEN: usize = 1 + SALT_LEN + NONCE_LEN + BODY_LEN + TAG_LEN;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ShareTokenError {
+    #[error("Share token is not valid base64")]
+    Encoding,
+    #[error("Share token version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("Share token has an invalid length, it may be truncated")]
+    Length,
+    #[error("Share token checksum mismatch, it may contain a typo")]
+    Checksum,
+    #[error("Share token is too large")]
+    Overflow,
+    #[error(transparent)]
+    Mnemonic(#[from] MnemonicError),
+    #[error("Share is passphrase protected, the passphrase is required")]
+    PassphraseRequired,
+    #[error("Invalid passphrase")]
+    Passphrase,
+    #[error("Passphrase key derivation did not complete")]
+    KeyDerivation,
+}
+
+/// How user shares are handed out: base64 tokens or SLIP-39 style word lists for paper
+/// custody. Both carry the same payload and are accepted wherever a share is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareFormat {
+    #[default]
+    Token,
+    Mnemonic,
+}
+
+/// User share as handed out to share holders.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareToken {
+    /// Bare base64 of `y`, issued before tokens were versioned.
+    Legacy { y: BigUint },
+    V1 {
+        key_id: Uuid,
+        x: BigUint,
+        y: BigUint,
+    },
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&keccak256(payload)[..CHECKSUM_LEN]);
+    checksum
+}
+
+fn wrapping_key(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, ShareTokenError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|_| ShareTokenError::Passphrase)?;
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(key.as_slice())))
+}
+
+/// Encrypts a token body with an Argon2id key, authenticating the version, salt and nonce.
+fn wrap(body: &[u8], passphrase: &str) -> Result<Vec<u8>, ShareTokenError> {
+    let mut header = vec![0u8; 1 + SALT_LEN + NONCE_LEN];
+    header[0] = WRAPPED_VERSION;
+    rand::thread_rng().fill_bytes(&mut header[1..]);
+
+    let (salt, nonce) = header[1..].split_at(SALT_LEN);
+
+    let ciphertext = wrapping_key(passphrase, salt)?
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: body,
+                aad: &header,
+            },
+        )
+        .map_err(|_| ShareTokenError::Passphrase)?;
+
+    Ok([header, ciphertext].concat())
+}
+
+fn unwrap(payload: &[u8], passphrase: &str) -> Result<Vec<u8>, ShareTokenError> {
+    if payload.len() != WRAPPED_LEN {
+        return Err(ShareTokenError::Length);
+    }
+
+    let (header, ciphertext) = payload.split_at(1 + SALT_LEN + NONCE_LEN);
+    let (salt, nonce) = header[1..].split_at(SALT_LEN);
+
+    wrapping_key(passphrase, salt)?
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| ShareTokenError::Passphrase)
+}
+
+fn to_fixed(value: &BigUint) -> Result<[u8; 32], ShareTokenError> {
+    let bytes = value.to_bytes_be();
+
+    if bytes.len() > 32 {
+        return Err(ShareTokenError::Overflow);
+    }
+
+    let mut fixed = [0u8; 32];
+    fixed[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(fixed)
+}
+
+impl ShareToken {
+    /// Encodes a user share, wrapping it when a passphrase is given so the token alone can
+    /// not be used to sign.
+    pub fn encode(
+        key_id: Uuid,
+        share: &ShareStore,
+        format: ShareFormat,
+        passphrase: Option<&str>,
+    ) -> Result<String, ShareTokenError> {
+        let x = BigUint::parse_bytes(share.x.as_bytes(), 16).ok_or(ShareTokenError::Encoding)?;
+        let y = BigUint::parse_bytes(share.y.as_bytes(), 16).ok_or(ShareTokenError::Encoding)?;
+
+        let mut payload = Vec::with_capacity(WRAPPED_LEN + CHECKSUM_LEN);
+        payload.push(VERSION);
+        payload.extend_from_slice(key_id.as_bytes());
+        payload.extend_from_slice(&to_fixed(&x)?);
+        payload.extend_from_slice(&to_fixed(&y)?);
+
+        if let Some(passphrase) = passphrase {
+            payload = wrap(&payload, passphrase)?;
+        }
+
+        if format == ShareFormat::Mnemonic {
+            // The mnemonic carries its own RS1024 checksum.
+            return Ok(mnemonic::encode(&payload));
+        }
+
+        payload.extend_from_slice(&checksum(&payload));
+
+        Ok(format!("{PREFIX}{}", URL_SAFE_NO_PAD.encode(payload)))
+    }
+
+    /// [`Self::encode`] for async callers. With a passphrase the Argon2id key derivation runs
+    /// on the blocking thread pool, so it does not stall the other requests of the worker.
+    pub async fn encode_blocking(
+        key_id: Uuid,
+        share: &ShareStore,
+        format: ShareFormat,
+        passphrase: Option<&str>,
+    ) -> Result<String, ShareTokenError> {
+        let Some(passphrase) = passphrase else {
+            return Self::encode(key_id, share, format, None);
+        };
+
+        let share = share.clone();
+        let passphrase = Zeroizing::new(passphrase.to_string());
+
+        tokio::task::spawn_blocking(move || {
+            Self::encode(key_id, &share, format, Some(passphrase.as_str()))
+        })
+        .await
+        .map_err(|_| ShareTokenError::KeyDerivation)?
+    }
+
+    /// [`Self::parse`] for async callers, see [`Self::encode_blocking`].
+    pub async fn parse_blocking(
+        token: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Self, ShareTokenError> {
+        let Some(passphrase) = passphrase else {
+            return Self::parse(token, None);
+        };
+
+        let token = Zeroizing::new(token.to_string());
+        let passphrase = Zeroizing::new(passphrase.to_string());
+
+        tokio::task::spawn_blocking(move || Self::parse(&token, Some(passphrase.as_str())))
+            .await
+            .map_err(|_| ShareTokenError::KeyDerivation)?
+    }
+
+    pub fn parse(token: &str, passphrase: Option<&str>) -> Result<Self, ShareTokenError> {
+        let token = token.trim();
+
+        if token.contains(char::is_whitespace) {
+            let length = if token.split_whitespace().count() == mnemonic::word_count(WRAPPED_LEN) {
+                WRAPPED_LEN
+            } else {
+                BODY_LEN
+            };
+
+            return Self::from_payload(&mnemonic::decode(token, length)?, passphrase);
+        }
+
+        let Some(encoded) = token.strip_prefix(PREFIX) else {
+            let y = STANDARD
+                .decode(token)
+                .map_err(|_| ShareTokenError::Encoding)?;
+
+            return Ok(ShareToken::Legacy {
+                y: BigUint::from_bytes_be(&y),
+            });
+        };
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| ShareTokenError::Encoding)?;
+
+        let length = match payload.first() {
+            Some(&VERSION) => BODY_LEN,
+            Some(&WRAPPED_VERSION) => WRAPPED_LEN,
+            Some(version) => return Err(ShareTokenError::UnsupportedVersion(*version)),
+            None => return Err(ShareTokenError::Length),
+        };
+
+        if payload.len() != length + CHECKSUM_LEN {
+            return Err(ShareTokenError::Length);
+        }
+
+        let (body, expected) = payload.split_at(length);
+
+        if checksum(body) != expected {
+            return Err(ShareTokenError::Checksum);
+        }
+
+        Self::from_payload(body, passphrase)
+    }
+
+    fn from_payload(payload: &[u8], passphrase: Option<&str>) -> Result<Self, ShareTokenError> {
+        if payload.first() != Some(&WRAPPED_VERSION) {
+            return Self::from_body(payload);
+        }
+
+        let passphrase = passphrase.ok_or(ShareTokenError::PassphraseRequired)?;
+
+        Self::from_body(&unwrap(payload, passphrase)?)
+    }
+
+    fn from_body(body: &[u8]) -> Result<Self, ShareTokenError> {
+        match body.first() {
+            Some(&VERSION) => {}
+            Some(version) => return Err(ShareTokenError::UnsupportedVersion(*version)),
+            None => return Err(ShareTokenError::Length),
+        }
+
+        if body.len() != BODY_LEN {
+            return Err(ShareTokenError::Length);
+        }
+
+        let key_id = Uuid::from_slice(&body[1..17]).map_err(|_| ShareTokenError::Length)?;
+
+        Ok(ShareToken::V1 {
+            key_id,
+            x: BigUint::from_bytes_be(&body[17..49]),
+            y: BigUint::from_bytes_be(&body[49..81]),
+        })
+    }
+
+    pub fn key_id(&self) -> Option<Uuid> {
+        match self {
+            ShareToken::Legacy { .. } => None,
+            ShareToken::V1 { key_id, .. } => Some(*key_id),
+        }
+    }
+
+    pub fn x(&self) -> Option<&BigUint> {
+        match self {
+            ShareToken::Legacy { .. } => None,
+            ShareToken::V1 { x, .. } => Some(x),
+        }
+    }
+
+    /// `y` in the hex form it is stored (and hashed) with in the shares table.
+    pub fn secret(&self) -> String {
+        match self {
+            ShareToken::Legacy { y } | ShareToken::V1 { y, .. } => hex::encode(y.to_bytes_be()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    use uuid::Uuid;
+
+    use crate::helpers::share_token::{ShareFormat, ShareToken, ShareTokenError};
+    use crate::services::polynomial::ShareStore;
+
+    #[test]
+    fn test_share_token() {
+        let key_id = Uuid::new_v4();
+        let share = ShareStore {
+            x: "01ff".to_string(),
+            y: "9c22ff5f21f0b81b113e63f7db6da94fedef11b2119b4088b89664fb9a3cb658".to_string(),
+        };
+
+        let token =
+            ShareToken::encode(key_id, &share, ShareFormat::Token, None).expect("Encode error");
+        let parsed = ShareToken::parse(&token, None).expect("Parse error");
+
+        assert_eq!(parsed.key_id(), Some(key_id));
+        assert_eq!(parsed.secret(), share.y);
+        assert_eq!(
+            parsed.x().map(|x| x.to_str_radix(16)),
+            Some("1ff".to_string())
+        );
+
+        let words =
+            ShareToken::encode(key_id, &share, ShareFormat::Mnemonic, None).expect("Encode error");
+        assert_eq!(words.split(' ').count(), 68);
+        assert_eq!(ShareToken::parse(&words, None), Ok(parsed.clone()));
+
+        for format in [ShareFormat::Token, ShareFormat::Mnemonic] {
+            let wrapped = ShareToken::encode(key_id, &share, format, Some("correct horse"))
+                .expect("Encode error");
+
+            assert_eq!(
+                ShareToken::parse(&wrapped, None),
+                Err(ShareTokenError::PassphraseRequired)
+            );
+            assert_eq!(
+                ShareToken::parse(&wrapped, Some("battery staple")),
+                Err(ShareTokenError::Passphrase)
+            );
+            assert_eq!(
+                ShareToken::parse(&wrapped, Some("correct horse")),
+                Ok(parsed.clone())
+            );
+        }
+
+        let legacy = STANDARD.encode(hex::decode(&share.y).unwrap());
+        let parsed = ShareToken::parse(&legacy, None).expect("Parse error");
+
+        assert_eq!(parsed.key_id(), None);
+        assert_eq!(parsed.secret(), share.y);
+
+        let mut typo = token.clone().into_bytes();
+        let index = typo.len() / 2;
+        typo[index] = if typo[index] == b'A' { b'B' } else { b'A' };
+
+        assert_eq!(
+            ShareToken::parse(&String::from_utf8(typo).unwrap(), None),
+            Err(ShareTokenError::Checksum)
+        );
+        let payload = URL_SAFE_NO_PAD.decode(&token[4..]).unwrap();
+        let truncated = format!("kms_{}", URL_SAFE_NO_PAD.encode(&payload[..80]));
+
+        assert_eq!(
+            ShareToken::parse(&truncated, None),
+            Err(ShareTokenError::Length)
+        );
+        assert_eq!(
+            ShareToken::parse("kms_Aw", None),
+            Err(ShareTokenError::UnsupportedVersion(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_share_token_blocking() {
+        let key_id = Uuid::new_v4();
+        let share = ShareStore {
+            x: "03".to_string(),
+            y: "9c22ff5f21f0b81b113e63f7db6da94fedef11b2119b4088b89664fb9a3cb658".to_string(),
+        };
+
+        let wrapped =
+            ShareToken::encode_blocking(key_id, &share, ShareFormat::Token, Some("correct horse"))
+                .await
+                .expect("Encode error");
+
+        assert_eq!(
+            ShareToken::parse_blocking(&wrapped, Some("battery staple")).await,
+            Err(ShareTokenError::Passphrase)
+        );
+        assert_eq!(
+            ShareToken::parse_blocking(&wrapped, Some("correct horse"))
+                .await
+                .map(|token| token.secret()),
+            Ok(share.y)
+        );
+    }
+}
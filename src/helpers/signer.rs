@@ -0,0 +1,55 @@
+use alloy::consensus::SignableTransaction;
+use alloy::network::TxSignerSync;
+use alloy::primitives::{Address, ChainId, Signature, B256};
+use alloy::signers::local::PrivateKeySigner;
+use alloy::signers::SignerSync;
+
+use crate::services::additive::AdditiveSigner;
+
+/// Signer restored from the shares of a key, depending on the configured
+/// [`SigningMode`](crate::SigningMode).
+pub enum KeySigner {
+    Local(PrivateKeySigner),
+    Additive(AdditiveSigner),
+}
+
+impl KeySigner {
+    pub fn address(&self) -> Address {
+        match self {
+            KeySigner::Local(signer) => signer.address(),
+            KeySigner::Additive(signer) => signer.address(),
+        }
+    }
+}
+
+impl SignerSync for KeySigner {
+    fn sign_hash_sync(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        match self {
+            KeySigner::Local(signer) => signer.sign_hash_sync(hash),
+            KeySigner::Additive(signer) => signer.sign_hash_sync(hash),
+        }
+    }
+
+    fn chain_id_sync(&self) -> Option<ChainId> {
+        match self {
+            KeySigner::Local(signer) => signer.chain_id_sync(),
+            KeySigner::Additive(signer) => signer.chain_id_sync(),
+        }
+    }
+}
+
+impl TxSignerSync<Signature> for KeySigner {
+    fn address(&self) -> Address {
+        KeySigner::address(self)
+    }
+
+    fn sign_transaction_sync(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        match self {
+            KeySigner::Local(signer) => signer.sign_transaction_sync(tx),
+            KeySigner::Additive(signer) => signer.sign_transaction_sync(tx),
+        }
+    }
+}
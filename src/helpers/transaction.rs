@@ -0,0 +1,35 @@
+use alloy::consensus::{SignableTransaction, TxEnvelope, TypedTransaction};
+use alloy::network::TxSignerSync;
+use alloy::primitives::Signature;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TransactionError {
+    #[error("Unsupported transaction type")]
+    Unsupported,
+    #[error("Signer error: {0}")]
+    Signer(#[from] alloy::signers::Error),
+}
+
+fn sign<S, T>(signer: &S, mut tx: T) -> Result<TxEnvelope, TransactionError>
+where
+    S: TxSignerSync<Signature>,
+    T: SignableTransaction<Signature>,
+    TxEnvelope: From<alloy::consensus::Signed<T>>,
+{
+    let signature = signer.sign_transaction_sync(&mut tx)?;
+
+    Ok(tx.into_signed(signature).into())
+}
+
+pub fn sign_transaction<S>(signer: &S, tx: TypedTransaction) -> Result<TxEnvelope, TransactionError>
+where
+    S: TxSignerSync<Signature>,
+{
+    match tx {
+        TypedTransaction::Legacy(tx) => sign(signer, tx),
+        TypedTransaction::Eip2930(tx) => sign(signer, tx),
+        TypedTransaction::Eip1559(tx) => sign(signer, tx),
+        TypedTransaction::Eip4844(_) => Err(TransactionError::Unsupported),
+    }
+}
@@ -1,9 +1,15 @@
 pub use app_data::AppData;
-pub use config::Config;
+pub use config::{Config, ShareStorageKind, SigningMode, VaultAuthMethod};
 pub use handlers::{
-    handlers, CreateUserResponse, KeysGenerateResponse, KeysRevokeRequest, SignMessageRequest,
-    SignMessageResponse,
+    handlers, CreateUserResponse, EncodedMessage, KeyType, KeysCheckResponse, KeysGenerateRequest,
+    KeysGenerateResponse, KeysGrantRequest, KeysRefreshRequest, KeysReshareRequest,
+    KeysRevokeRequest, KeysRollbackRequest, KeysRollbackResponse, KeysShareResponse,
+    MessageEncoding, ShareFormat, SignBatchItem, SignBatchRequest, SignBatchResponse,
+    SignHashRequest, SignHashResponse, SignMessageRequest, SignMessageResponse, SignSchnorrRequest,
+    SignSchnorrResponse, SignTransactionRequest, SignTransactionResponse, SignTypedDataRequest,
+    VerifyPayload, VerifyRequest, VerifyResponse,
 };
+pub use helpers::local_share::encrypt_local_shares;
 
 mod app_data;
 mod config;
@@ -0,0 +1,27 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A superseded sharing of a key, kept to roll the key back to an earlier version of its
+/// cloud share.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "key_versions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub key_id: Uuid,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub cloud_version: i64,
+    pub local_key: String,
+    pub local_index: String,
+    pub local_dek: Option<String>,
+    pub commitments: Option<Json>,
+    pub total_shares: i32,
+    pub threshold: i32,
+    /// Shares revoked when this sharing was replaced.
+    pub share_ids: Json,
+    pub created_at: chrono::DateTime<chrono::FixedOffset>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -9,8 +9,21 @@ pub struct Model {
     pub user_id: Uuid,
     pub local_key: String,
     pub local_index: String,
+    /// Data key encrypting `local_key` and `local_index`, wrapped by the key-encryption key.
+    /// `None` for local shares stored in plaintext.
+    pub local_dek: Option<String>,
     pub cloud_key: String,
+    /// Version of the cloud share at `cloud_key`, `None` when the share storage keeps no
+    /// versions.
+    pub cloud_version: Option<i64>,
     pub address: String,
+    pub allow_raw_hash: bool,
+    /// Whether a signer reconstructed from this key may be kept in the signer cache.
+    pub allow_signer_cache: bool,
+    pub total_shares: i32,
+    pub threshold: i32,
+    pub commitments: Option<Json>,
+    pub key_type: KeyType,
     pub updated_at: chrono::DateTime<chrono::FixedOffset>,
     pub created_at: chrono::DateTime<chrono::FixedOffset>,
 }
@@ -19,3 +32,16 @@ pub struct Model {
 pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(
+    Clone, Copy, Debug, Default, Serialize, Deserialize, EnumIter, DeriveActiveEnum, PartialEq,
+)]
+#[serde(rename_all = "camelCase")]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "key_type")]
+pub enum KeyType {
+    #[default]
+    #[sea_orm(string_value = "ecdsa")]
+    Ecdsa,
+    #[sea_orm(string_value = "schnorr")]
+    Schnorr,
+}
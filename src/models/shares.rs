@@ -28,6 +28,8 @@ pub enum SharesOwner {
     Admin,
     #[sea_orm(string_value = "guest")]
     Guest,
+    #[sea_orm(string_value = "recovery")]
+    Recovery,
     #[default]
     #[sea_orm(string_value = "unknown")]
     Unknown,
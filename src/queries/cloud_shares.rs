@@ -0,0 +1,74 @@
+use chrono::Utc;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, ConnectionTrait, DbErr, EntityTrait};
+use thiserror::Error;
+use tracing::instrument;
+
+use crate::models::cloud_shares::{ActiveModel, Column, Entity, Model};
+
+#[derive(Debug, Error)]
+pub enum CloudShareErrors {
+    #[error("Cloud share not found: {0}")]
+    NotFound(String),
+    #[error("Cloud share already exists: {0}")]
+    Exists(String),
+    #[error("DbErr: {0}")]
+    DbErr(DbErr),
+}
+
+#[instrument(
+    level = "debug",
+    name = "create_cloud_share",
+    skip(ciphertext, connection)
+)]
+pub async fn create_cloud_share<D>(
+    path: &str,
+    ciphertext: String,
+    connection: &D,
+) -> Result<(), CloudShareErrors>
+where
+    D: ConnectionTrait,
+{
+    let model = ActiveModel {
+        path: ActiveValue::Set(path.to_string()),
+        ciphertext: ActiveValue::Set(ciphertext),
+        created_at: ActiveValue::Set(Utc::now().into()),
+        updated_at: ActiveValue::Set(Utc::now().into()),
+    };
+
+    let inserted = Entity::insert(model)
+        .on_conflict(OnConflict::column(Column::Path).do_nothing().to_owned())
+        .exec_without_returning(connection)
+        .await
+        .map_err(CloudShareErrors::DbErr)?;
+
+    match inserted {
+        0 => Err(CloudShareErrors::Exists(path.to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[instrument(level = "debug", name = "get_cloud_share", skip(connection))]
+pub async fn get_cloud_share<D>(path: &str, connection: &D) -> Result<Model, CloudShareErrors>
+where
+    D: ConnectionTrait,
+{
+    match Entity::find_by_id(path.to_string()).one(connection).await {
+        Ok(Some(share)) => Ok(share),
+        Ok(None) => Err(CloudShareErrors::NotFound(path.to_string())),
+        Err(err) => Err(CloudShareErrors::DbErr(err)),
+    }
+}
+
+#[instrument(level = "debug", name = "delete_cloud_share", skip(connection))]
+pub async fn delete_cloud_share<D>(path: &str, connection: &D) -> Result<(), CloudShareErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::delete_by_id(path.to_string())
+        .exec(connection)
+        .await
+        .map_err(CloudShareErrors::DbErr)?;
+
+    Ok(())
+}
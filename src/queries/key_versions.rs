@@ -0,0 +1,206 @@
+use chrono::Utc;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use thiserror::Error;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::models::key_versions::{ActiveModel, Column, Entity, Model};
+use crate::models::keys::Model as KeyModel;
+use crate::queries::keys::UpdateLocalShare;
+
+#[derive(Debug, Error)]
+pub enum KeyVersionErrors {
+    #[error("Key version not found: {0}")]
+    NotFound(String),
+    #[error("DbErr: {0}")]
+    DbErr(DbErr),
+}
+
+/// Keeps the current sharing of `key` at its cloud version, with the shares revoked when
+/// it is replaced. A sharing that was rolled back and forth is overwritten.
+#[instrument(level = "debug", name = "save_key_version", skip(key, connection))]
+pub async fn save_key_version<D>(
+    key: &KeyModel,
+    cloud_version: i64,
+    share_ids: &[Uuid],
+    connection: &D,
+) -> Result<(), KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    let model = ActiveModel {
+        key_id: ActiveValue::Set(key.id),
+        cloud_version: ActiveValue::Set(cloud_version),
+        local_key: ActiveValue::Set(key.local_key.clone()),
+        local_index: ActiveValue::Set(key.local_index.clone()),
+        local_dek: ActiveValue::Set(key.local_dek.clone()),
+        commitments: ActiveValue::Set(key.commitments.clone()),
+        total_shares: ActiveValue::Set(key.total_shares),
+        threshold: ActiveValue::Set(key.threshold),
+        share_ids: ActiveValue::Set(serde_json::json!(share_ids)),
+        created_at: ActiveValue::Set(Utc::now().into()),
+    };
+
+    Entity::insert(model)
+        .on_conflict(
+            OnConflict::columns([Column::KeyId, Column::CloudVersion])
+                .update_columns([
+                    Column::LocalKey,
+                    Column::LocalIndex,
+                    Column::LocalDek,
+                    Column::Commitments,
+                    Column::TotalShares,
+                    Column::Threshold,
+                    Column::ShareIds,
+                    Column::CreatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec_without_returning(connection)
+        .await
+        .map_err(KeyVersionErrors::DbErr)?;
+
+    Ok(())
+}
+
+#[instrument(level = "debug", name = "get_key_version", skip(connection))]
+pub async fn get_key_version<D>(
+    key_id: &Uuid,
+    cloud_version: i64,
+    connection: &D,
+) -> Result<Model, KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    match Entity::find_by_id((*key_id, cloud_version))
+        .one(connection)
+        .await
+    {
+        Ok(Some(version)) => Ok(version),
+        Ok(None) => Err(KeyVersionErrors::NotFound(format!(
+            "{key_id} version {cloud_version}"
+        ))),
+        Err(err) => Err(KeyVersionErrors::DbErr(err)),
+    }
+}
+
+/// Drops a kept sharing, e.g. once a rollback made it the current one again.
+#[instrument(level = "debug", name = "delete_key_version", skip(connection))]
+pub async fn delete_key_version<D>(
+    key_id: &Uuid,
+    cloud_version: i64,
+    connection: &D,
+) -> Result<(), KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::delete_by_id((*key_id, cloud_version))
+        .exec(connection)
+        .await
+        .map_err(KeyVersionErrors::DbErr)?;
+
+    Ok(())
+}
+
+/// Drops every kept sharing of the key and returns their cloud versions.
+#[instrument(level = "debug", name = "delete_key_versions", skip(connection))]
+pub async fn delete_key_versions<D>(
+    key_id: &Uuid,
+    connection: &D,
+) -> Result<Vec<i64>, KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    prune_key_versions(key_id, 0, connection).await
+}
+
+/// Keeps the `retained` most recently superseded sharings of the key, drops the others and
+/// returns their cloud versions.
+#[instrument(level = "debug", name = "prune_key_versions", skip(connection))]
+pub async fn prune_key_versions<D>(
+    key_id: &Uuid,
+    retained: usize,
+    connection: &D,
+) -> Result<Vec<i64>, KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    let versions: Vec<i64> = Entity::find()
+        .select_only()
+        .column(Column::CloudVersion)
+        .filter(Column::KeyId.eq(*key_id))
+        .order_by_desc(Column::CreatedAt)
+        .order_by_desc(Column::CloudVersion)
+        .into_tuple()
+        .all(connection)
+        .await
+        .map_err(KeyVersionErrors::DbErr)?;
+
+    let pruned = versions.into_iter().skip(retained).collect::<Vec<_>>();
+
+    if !pruned.is_empty() {
+        Entity::delete_many()
+            .filter(Column::KeyId.eq(*key_id))
+            .filter(Column::CloudVersion.is_in(pruned.clone()))
+            .exec(connection)
+            .await
+            .map_err(KeyVersionErrors::DbErr)?;
+    }
+
+    Ok(pruned)
+}
+
+#[instrument(
+    level = "debug",
+    name = "get_key_versions_with_plaintext_local_share",
+    skip(connection)
+)]
+pub async fn get_key_versions_with_plaintext_local_share<D>(
+    connection: &D,
+) -> Result<Vec<Model>, KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::find()
+        .filter(Column::LocalDek.is_null())
+        .all(connection)
+        .await
+        .map_err(KeyVersionErrors::DbErr)
+}
+
+/// Encrypts the plaintext local share of a kept sharing in place, like
+/// [`crate::queries::keys::update_plaintext_local_share`].
+#[instrument(
+    level = "debug",
+    name = "update_plaintext_key_version_local_share",
+    skip(connection)
+)]
+pub async fn update_plaintext_key_version_local_share<D>(
+    key_id: &Uuid,
+    cloud_version: i64,
+    data: UpdateLocalShare,
+    connection: &D,
+) -> Result<bool, KeyVersionErrors>
+where
+    D: ConnectionTrait,
+{
+    let result = Entity::update_many()
+        .set(ActiveModel {
+            local_key: ActiveValue::Set(data.local_key),
+            local_index: ActiveValue::Set(data.local_index),
+            local_dek: ActiveValue::Set(data.local_dek),
+            ..Default::default()
+        })
+        .filter(Column::KeyId.eq(*key_id))
+        .filter(Column::CloudVersion.eq(cloud_version))
+        .filter(Column::LocalDek.is_null())
+        .exec(connection)
+        .await
+        .map_err(KeyVersionErrors::DbErr)?;
+
+    Ok(result.rows_affected == 1)
+}
@@ -1,11 +1,12 @@
-use alloy::primitives::Address;
 use chrono::Utc;
-use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbErr, EntityTrait};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+};
 use thiserror::Error;
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::models::keys::{ActiveModel, Entity, Model};
+use crate::models::keys::{ActiveModel, Column, Entity, KeyType, Model};
 
 #[derive(Debug, Error)]
 pub enum KeyErrors {
@@ -17,11 +18,20 @@ pub enum KeyErrors {
 
 #[derive(Debug)]
 pub struct CreateOrUpdateKey {
+    pub id: Uuid,
     pub user_id: Uuid,
     pub local_key: String,
     pub local_index: String,
+    pub local_dek: Option<String>,
     pub cloud_key: String,
-    pub address: Address,
+    pub cloud_version: Option<i64>,
+    pub address: String,
+    pub allow_raw_hash: bool,
+    pub allow_signer_cache: bool,
+    pub total_shares: i32,
+    pub threshold: i32,
+    pub commitments: Vec<String>,
+    pub key_type: KeyType,
 }
 
 #[instrument(level = "debug", name = "create_key", skip(connection))]
@@ -30,12 +40,20 @@ where
     D: ConnectionTrait,
 {
     let model = ActiveModel {
-        id: ActiveValue::Set(Uuid::new_v4()),
+        id: ActiveValue::Set(data.id),
         user_id: ActiveValue::Set(data.user_id),
         local_key: ActiveValue::Set(data.local_key),
         local_index: ActiveValue::Set(data.local_index),
+        local_dek: ActiveValue::Set(data.local_dek),
         cloud_key: ActiveValue::Set(data.cloud_key),
-        address: ActiveValue::Set(data.address.to_string()),
+        cloud_version: ActiveValue::Set(data.cloud_version),
+        address: ActiveValue::Set(data.address),
+        allow_raw_hash: ActiveValue::Set(data.allow_raw_hash),
+        allow_signer_cache: ActiveValue::Set(data.allow_signer_cache),
+        total_shares: ActiveValue::Set(data.total_shares),
+        threshold: ActiveValue::Set(data.threshold),
+        commitments: ActiveValue::Set(Some(serde_json::json!(data.commitments))),
+        key_type: ActiveValue::Set(data.key_type),
         created_at: ActiveValue::Set(Utc::now().into()),
         updated_at: ActiveValue::Set(Utc::now().into()),
     };
@@ -54,3 +72,113 @@ where
         Err(err) => Err(KeyErrors::DbErr(err)),
     }
 }
+
+#[derive(Debug)]
+pub struct UpdateKeyShares {
+    pub local_key: String,
+    pub local_index: String,
+    pub local_dek: Option<String>,
+    pub cloud_key: String,
+    pub cloud_version: Option<i64>,
+    pub commitments: Option<Vec<String>>,
+    pub total_shares: i32,
+    pub threshold: i32,
+}
+
+#[instrument(level = "debug", name = "update_key_shares", skip(connection))]
+pub async fn update_key_shares<D>(
+    id: &Uuid,
+    data: UpdateKeyShares,
+    connection: &D,
+) -> Result<Model, KeyErrors>
+where
+    D: ConnectionTrait,
+{
+    let model = get_key_by_id(id, connection).await?;
+
+    let mut row: ActiveModel = model.into();
+
+    row.local_key = ActiveValue::Set(data.local_key);
+    row.local_index = ActiveValue::Set(data.local_index);
+    row.local_dek = ActiveValue::Set(data.local_dek);
+    row.cloud_key = ActiveValue::Set(data.cloud_key);
+    row.cloud_version = ActiveValue::Set(data.cloud_version);
+    row.commitments = ActiveValue::Set(
+        data.commitments
+            .map(|commitments| serde_json::json!(commitments)),
+    );
+    row.total_shares = ActiveValue::Set(data.total_shares);
+    row.threshold = ActiveValue::Set(data.threshold);
+    row.updated_at = ActiveValue::Set(Utc::now().into());
+
+    row.update(connection).await.map_err(KeyErrors::DbErr)
+}
+
+#[instrument(
+    level = "debug",
+    name = "get_keys_with_plaintext_local_share",
+    skip(connection)
+)]
+pub async fn get_keys_with_plaintext_local_share<D>(connection: &D) -> Result<Vec<Model>, KeyErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::find()
+        .filter(Column::LocalDek.is_null())
+        .all(connection)
+        .await
+        .map_err(KeyErrors::DbErr)
+}
+
+/// Whether any local share is encrypted, i.e. a key-encryption key is expected.
+#[instrument(level = "debug", name = "has_encrypted_local_shares", skip(connection))]
+pub async fn has_encrypted_local_shares<D>(connection: &D) -> Result<bool, KeyErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::find()
+        .filter(Column::LocalDek.is_not_null())
+        .one(connection)
+        .await
+        .map(|key| key.is_some())
+        .map_err(KeyErrors::DbErr)
+}
+
+#[derive(Debug)]
+pub struct UpdateLocalShare {
+    pub local_key: String,
+    pub local_index: String,
+    pub local_dek: Option<String>,
+}
+
+/// Encrypts a plaintext local share in place. Rows that got a data key in the meantime,
+/// e.g. from a refresh on another instance, are left untouched.
+#[instrument(
+    level = "debug",
+    name = "update_plaintext_local_share",
+    skip(connection)
+)]
+pub async fn update_plaintext_local_share<D>(
+    id: &Uuid,
+    data: UpdateLocalShare,
+    connection: &D,
+) -> Result<bool, KeyErrors>
+where
+    D: ConnectionTrait,
+{
+    let result = Entity::update_many()
+        .set(ActiveModel {
+            local_key: ActiveValue::Set(data.local_key),
+            local_index: ActiveValue::Set(data.local_index),
+            local_dek: ActiveValue::Set(data.local_dek),
+            updated_at: ActiveValue::Set(Utc::now().into()),
+            ..Default::default()
+        })
+        .filter(Column::Id.eq(*id))
+        .filter(Column::LocalDek.is_null())
+        .exec(connection)
+        .await
+        .map_err(KeyErrors::DbErr)?;
+
+    Ok(result.rows_affected == 1)
+}
@@ -19,8 +19,6 @@ pub struct CreateLog {
 
 #[derive(Debug, Error)]
 pub enum LogErrors {
-    #[error("Log not found: {0}")]
-    NotFound(String),
     #[error("DbErr: {0}")]
     DbErr(DbErr),
 }
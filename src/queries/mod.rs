@@ -1,3 +1,5 @@
+pub mod cloud_shares;
+pub mod key_versions;
 pub mod keys;
 pub mod logs;
 pub mod shares;
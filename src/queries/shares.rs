@@ -1,6 +1,7 @@
 use chrono::Utc;
 use sea_orm::{
     ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    QuerySelect,
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -76,6 +77,26 @@ where
     }
 }
 
+#[instrument(
+    level = "debug",
+    name = "get_granted_shares_by_key_id",
+    skip(connection)
+)]
+pub async fn get_granted_shares_by_key_id<D>(
+    key_id: &Uuid,
+    connection: &D,
+) -> Result<Vec<Model>, ShareErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::find()
+        .filter(Column::KeyId.eq(*key_id))
+        .filter(Column::Status.eq(SharesStatus::Granted))
+        .all(connection)
+        .await
+        .map_err(ShareErrors::DbErr)
+}
+
 #[instrument(level = "debug", name = "revoke_share_by_id", skip(connection))]
 pub async fn revoke_share_by_id<D>(id: &Uuid, connection: &D) -> Result<Model, ShareErrors>
 where
@@ -90,3 +111,92 @@ where
 
     row.update(connection).await.map_err(ShareErrors::DbErr)
 }
+
+#[instrument(level = "debug", name = "revoke_shares_by_key_id", skip(connection))]
+pub async fn revoke_shares_by_key_id<D>(
+    key_id: &Uuid,
+    connection: &D,
+) -> Result<Vec<Uuid>, ShareErrors>
+where
+    D: ConnectionTrait,
+{
+    let ids: Vec<Uuid> = Entity::find()
+        .select_only()
+        .column(Column::Id)
+        .filter(Column::KeyId.eq(*key_id))
+        .filter(Column::Status.eq(SharesStatus::Granted))
+        .into_tuple()
+        .all(connection)
+        .await
+        .map_err(ShareErrors::DbErr)?;
+
+    set_shares_status(&ids, SharesStatus::Revoked, connection).await?;
+
+    Ok(ids)
+}
+
+/// Grants shares revoked by a refresh or reshare again, when rolling a key back.
+#[instrument(level = "debug", name = "grant_shares_by_ids", skip(connection))]
+pub async fn grant_shares_by_ids<D>(ids: &[Uuid], connection: &D) -> Result<u64, ShareErrors>
+where
+    D: ConnectionTrait,
+{
+    set_shares_status(ids, SharesStatus::Granted, connection).await
+}
+
+/// Revokes the recovery shares among `ids` once they recovered a key. Returns the revoked
+/// ids, or `None` when one of them is no longer granted, e.g. used by a concurrent recovery.
+#[instrument(level = "debug", name = "consume_recovery_shares", skip(connection))]
+pub async fn consume_recovery_shares<D>(
+    ids: &[Uuid],
+    connection: &D,
+) -> Result<Option<Vec<Uuid>>, ShareErrors>
+where
+    D: ConnectionTrait,
+{
+    let recovery: Vec<Uuid> = Entity::find()
+        .select_only()
+        .column(Column::Id)
+        .filter(Column::Id.is_in(ids.iter().copied()))
+        .filter(Column::Owner.eq(SharesOwner::Recovery))
+        .into_tuple()
+        .all(connection)
+        .await
+        .map_err(ShareErrors::DbErr)?;
+
+    let revoked = Entity::update_many()
+        .set(ActiveModel {
+            status: ActiveValue::Set(SharesStatus::Revoked),
+            updated_at: ActiveValue::Set(Utc::now().into()),
+            ..Default::default()
+        })
+        .filter(Column::Id.is_in(recovery.iter().copied()))
+        .filter(Column::Status.eq(SharesStatus::Granted))
+        .exec(connection)
+        .await
+        .map_err(ShareErrors::DbErr)?
+        .rows_affected;
+
+    Ok((revoked == recovery.len() as u64).then_some(recovery))
+}
+
+async fn set_shares_status<D>(
+    ids: &[Uuid],
+    status: SharesStatus,
+    connection: &D,
+) -> Result<u64, ShareErrors>
+where
+    D: ConnectionTrait,
+{
+    Entity::update_many()
+        .set(ActiveModel {
+            status: ActiveValue::Set(status),
+            updated_at: ActiveValue::Set(Utc::now().into()),
+            ..Default::default()
+        })
+        .filter(Column::Id.is_in(ids.iter().copied()))
+        .exec(connection)
+        .await
+        .map(|result| result.rows_affected)
+        .map_err(ShareErrors::DbErr)
+}
@@ -0,0 +1,246 @@
+use alloy::consensus::SignableTransaction;
+use alloy::network::TxSignerSync;
+use alloy::primitives::{Address, ChainId, Signature, B256};
+use alloy::signers::SignerSync;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::scalar::IsHigh;
+use k256::elliptic_curve::Field;
+use k256::{FieldBytes, ProjectivePoint, Scalar, U256};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::services::polynomial::{additive_shares, Share};
+
+#[derive(Debug, Error)]
+pub enum AdditiveError {
+    #[error("At least two parties are required")]
+    NotEnoughParties,
+    #[error("Duplicate share index")]
+    DuplicateIndex,
+    #[error("Signing round produced an invalid nonce")]
+    InvalidNonce,
+    #[error("Signature does not match the key address")]
+    AddressMismatch,
+}
+
+fn random_scalar() -> Scalar {
+    Scalar::random(&mut rand::thread_rng())
+}
+
+/// Multiplicative-to-additive conversion: splits `a * b` into two random summands, one for
+/// each party. All parties live in this process, so the conversion is computed directly
+/// instead of over Paillier ciphertexts and hides nothing from the process itself.
+fn mta(a: &Scalar, b: &Scalar) -> (Zeroizing<Scalar>, Zeroizing<Scalar>) {
+    let beta = Zeroizing::new(random_scalar());
+
+    (Zeroizing::new(a * b - *beta), beta)
+}
+
+/// One signing party. `secret` is the Lagrange-weighted share: the private key is the sum of
+/// all party secrets, which this module never adds up.
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct Party {
+    secret: Scalar,
+    k: Scalar,
+    gamma: Scalar,
+}
+
+/// In-process additive signing, not a threshold mode. The cloud, local and user shares of a
+/// key become additive shares of the private key, and an ECDSA signature is computed from
+/// them in GG18-shaped rounds that only combine nonce and key shares through MtA conversions.
+///
+/// No round leaves this process and MtA is computed in the clear, so the process holds all
+/// key shares at once and anyone able to read its memory can still add them up to the
+/// private key. Compared to the `reconstruct` signing mode it only avoids materialising the
+/// key as a single value; it does not remove reconstruction as a risk. Secrets, nonces and
+/// intermediates are zeroized once a signature is done.
+pub struct AdditiveSigner {
+    secrets: Zeroizing<Vec<Scalar>>,
+    address: Address,
+}
+
+impl AdditiveSigner {
+    pub fn new(shares: &[Share], address: Address) -> Result<Self, AdditiveError> {
+        if shares.len() < 2 {
+            return Err(AdditiveError::NotEnoughParties);
+        }
+
+        let secrets = Zeroizing::new(additive_shares(shares).ok_or(AdditiveError::DuplicateIndex)?);
+
+        Ok(AdditiveSigner { secrets, address })
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sign_prehash(&self, hash: &B256) -> Result<Signature, AdditiveError> {
+        let parties = self
+            .secrets
+            .iter()
+            .map(|secret| Party {
+                secret: *secret,
+                k: random_scalar(),
+                gamma: random_scalar(),
+            })
+            .collect::<Vec<_>>();
+
+        // Round 1: every party commits to gamma_i * G.
+        let gamma_point = parties
+            .iter()
+            .fold(ProjectivePoint::IDENTITY, |acc, party| {
+                acc + ProjectivePoint::GENERATOR * party.gamma
+            });
+
+        // Round 2: pairwise MtA gives each party additive shares of k * gamma and k * x.
+        let mut deltas = Zeroizing::new(
+            parties
+                .iter()
+                .map(|party| party.k * party.gamma)
+                .collect::<Vec<_>>(),
+        );
+        let mut sigmas = Zeroizing::new(
+            parties
+                .iter()
+                .map(|party| party.k * party.secret)
+                .collect::<Vec<_>>(),
+        );
+
+        for (i, party_i) in parties.iter().enumerate() {
+            for (j, party_j) in parties.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+
+                let (alpha, beta) = mta(&party_i.k, &party_j.gamma);
+                deltas[i] += *alpha;
+                deltas[j] += *beta;
+
+                let (mu, nu) = mta(&party_i.k, &party_j.secret);
+                sigmas[i] += *mu;
+                sigmas[j] += *nu;
+            }
+        }
+
+        // Round 3: delta = k * gamma is published, R = delta^-1 * Gamma = k^-1 * G.
+        let delta = deltas.iter().fold(Scalar::ZERO, |acc, delta| acc + delta);
+        let delta_inv =
+            Option::<Scalar>::from(delta.invert()).ok_or(AdditiveError::InvalidNonce)?;
+
+        let r_point = (gamma_point * delta_inv).to_affine();
+        let r = <Scalar as Reduce<U256>>::reduce_bytes(&r_point.x());
+
+        if bool::from(r.is_zero()) {
+            return Err(AdditiveError::InvalidNonce);
+        }
+
+        // Round 4: s_i = m * k_i + r * sigma_i, and s = k * (m + r * x).
+        let message =
+            <Scalar as Reduce<U256>>::reduce_bytes(FieldBytes::from_slice(hash.as_slice()));
+
+        let mut s = parties
+            .iter()
+            .zip(sigmas.iter())
+            .fold(Scalar::ZERO, |acc, (party, sigma)| {
+                acc + message * party.k + r * sigma
+            });
+
+        if bool::from(s.is_zero()) {
+            return Err(AdditiveError::InvalidNonce);
+        }
+
+        let mut parity = bool::from(r_point.y_is_odd());
+
+        if bool::from(s.is_high()) {
+            s = -s;
+            parity = !parity;
+        }
+
+        let signature = Signature::from_scalars_and_parity(
+            B256::from_slice(&r.to_bytes()),
+            B256::from_slice(&s.to_bytes()),
+            parity,
+        )
+        .map_err(|_| AdditiveError::InvalidNonce)?;
+
+        match signature.recover_address_from_prehash(hash) {
+            Ok(address) if address == self.address => Ok(signature),
+            _ => Err(AdditiveError::AddressMismatch),
+        }
+    }
+}
+
+impl SignerSync for AdditiveSigner {
+    fn sign_hash_sync(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        self.sign_prehash(hash)
+            .map_err(alloy::signers::Error::other)
+    }
+
+    fn chain_id_sync(&self) -> Option<ChainId> {
+        None
+    }
+}
+
+impl TxSignerSync<Signature> for AdditiveSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sign_transaction_sync(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        let mut signature = self.sign_hash_sync(&tx.signature_hash())?;
+
+        if tx.use_eip155() {
+            if let Some(chain_id) = tx.chain_id() {
+                signature = signature.with_chain_id(chain_id);
+            }
+        }
+
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::keccak256;
+    use alloy::signers::local::PrivateKeySigner;
+    use alloy::signers::SignerSync;
+    use num_bigint::BigUint;
+
+    use crate::helpers::generate_code::generate_random;
+    use crate::services::additive::AdditiveSigner;
+    use crate::services::polynomial::Polynomial;
+
+    #[test]
+    fn test_additive_signer() {
+        let sss = Polynomial::new();
+
+        let private_key = generate_random();
+        let signer = PrivateKeySigner::from_slice(private_key.as_slice()).expect("Invalid key");
+        let secret = BigUint::from_bytes_be(private_key.as_slice());
+
+        let (shares, _) = sss.generate_shares(&secret, 5, 3);
+
+        let additive_signer =
+            AdditiveSigner::new(&shares[1..4], signer.address()).expect("Invalid shares");
+
+        let hash = keccak256("Hello, world!");
+        let signature = additive_signer
+            .sign_hash_sync(&hash)
+            .expect("Failed to sign");
+
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash).unwrap(),
+            signer.address()
+        );
+
+        let mut shares = shares[0..3].to_vec();
+        shares[2].y += 1u32;
+
+        let corrupted = AdditiveSigner::new(&shares, signer.address()).expect("Invalid shares");
+        assert!(corrupted.sign_hash_sync(&hash).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use alloy::primitives::B256;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::{Field, PrimeField};
+use k256::schnorr::signature::hazmat::PrehashVerifier;
+use k256::schnorr::{Signature, VerifyingKey};
+use k256::{ProjectivePoint, Scalar, U256};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::services::polynomial::{additive_shares, to_scalar, Share};
+
+const BINDING_TAG: &[u8] = b"FROST/secp256k1/rho";
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+#[derive(Debug, Error)]
+pub enum FrostError {
+    #[error("At least two parties are required")]
+    NotEnoughParties,
+    #[error("Duplicate share index")]
+    DuplicateIndex,
+    #[error("Invalid public key")]
+    PublicKey,
+    #[error("Signature does not match the key")]
+    InvalidSignature,
+}
+
+fn tagged_hash(tag: &[u8]) -> Sha256 {
+    let tag_hash = Sha256::digest(tag);
+
+    Sha256::new().chain_update(tag_hash).chain_update(tag_hash)
+}
+
+fn hash_to_scalar(hash: Sha256) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&hash.finalize())
+}
+
+/// Turns random key material into a BIP-340 secret: the secret is negated when its public
+/// key has an odd y coordinate, so every share already belongs to the x-only key.
+pub fn xonly_secret(private_key: &[u8]) -> Option<(BigUint, [u8; 32])> {
+    let secret = to_scalar(&BigUint::from_bytes_be(private_key));
+
+    if bool::from(secret.is_zero()) {
+        return None;
+    }
+
+    let public_key = (ProjectivePoint::GENERATOR * secret).to_affine();
+
+    let secret = if bool::from(public_key.y_is_odd()) {
+        -secret
+    } else {
+        secret
+    };
+
+    Some((
+        BigUint::from_bytes_be(&secret.to_repr()),
+        public_key.x().into(),
+    ))
+}
+
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct Participant {
+    index: Scalar,
+    secret: Scalar,
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+/// FROST signer producing BIP-340 signatures from the cloud, local and user shares of a key.
+/// Each participant only contributes a partial signature `z_i` and the key is never summed
+/// up.
+///
+/// Participants run in this process like the parties of
+/// [`crate::services::additive::AdditiveSigner`], whose doc covers what that protects against.
+pub struct FrostSigner {
+    indexes: Vec<Scalar>,
+    secrets: Zeroizing<Vec<Scalar>>,
+    public_key: VerifyingKey,
+}
+
+impl FrostSigner {
+    pub fn new(shares: &[Share], public_key: &[u8]) -> Result<Self, FrostError> {
+        if shares.len() < 2 {
+            return Err(FrostError::NotEnoughParties);
+        }
+
+        let secrets = Zeroizing::new(additive_shares(shares).ok_or(FrostError::DuplicateIndex)?);
+        let public_key = VerifyingKey::from_bytes(public_key).map_err(|_| FrostError::PublicKey)?;
+
+        Ok(FrostSigner {
+            indexes: shares.iter().map(|share| to_scalar(&share.x)).collect(),
+            secrets,
+            public_key,
+        })
+    }
+
+    pub fn sign(&self, message: &B256) -> Result<[u8; 64], FrostError> {
+        let rng = &mut rand::thread_rng();
+
+        let participants = self
+            .indexes
+            .iter()
+            .zip(self.secrets.iter())
+            .map(|(index, secret)| Participant {
+                index: *index,
+                secret: *secret,
+                hiding: Scalar::random(&mut *rng),
+                binding: Scalar::random(&mut *rng),
+            })
+            .collect::<Vec<_>>();
+
+        // Round 1: every participant publishes its nonce commitments (D_i, E_i).
+        let commitments = participants
+            .iter()
+            .map(|participant| {
+                (
+                    ProjectivePoint::GENERATOR * participant.hiding,
+                    ProjectivePoint::GENERATOR * participant.binding,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded_commitments = Sha256::new();
+
+        for (participant, (hiding, binding)) in participants.iter().zip(&commitments) {
+            encoded_commitments.update(participant.index.to_repr());
+            encoded_commitments.update(hiding.to_affine().to_encoded_point(true));
+            encoded_commitments.update(binding.to_affine().to_encoded_point(true));
+        }
+
+        let encoded_commitments = encoded_commitments.finalize();
+
+        // Round 2: binding factors tie every nonce to the message and the full commitment list.
+        let rhos = participants
+            .iter()
+            .map(|participant| {
+                hash_to_scalar(
+                    tagged_hash(BINDING_TAG)
+                        .chain_update(participant.index.to_repr())
+                        .chain_update(message)
+                        .chain_update(encoded_commitments),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let group_commitment = commitments
+            .iter()
+            .zip(&rhos)
+            .fold(
+                ProjectivePoint::IDENTITY,
+                |acc, ((hiding, binding), rho)| acc + hiding + binding * rho,
+            )
+            .to_affine();
+
+        // BIP-340 only accepts nonces with an even y coordinate, so the participants negate
+        // their nonces instead of the group commitment.
+        let negate_nonces = bool::from(group_commitment.y_is_odd());
+        let r: [u8; 32] = group_commitment.x().into();
+
+        let challenge = hash_to_scalar(
+            tagged_hash(CHALLENGE_TAG)
+                .chain_update(r)
+                .chain_update(self.public_key.to_bytes())
+                .chain_update(message),
+        );
+
+        let z = participants
+            .iter()
+            .zip(&rhos)
+            .fold(Scalar::ZERO, |acc, (participant, rho)| {
+                let mut nonce = Zeroizing::new(participant.hiding + participant.binding * rho);
+
+                if negate_nonces {
+                    *nonce = -*nonce;
+                }
+
+                acc + *nonce + participant.secret * challenge
+            });
+
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&r);
+        signature[32..].copy_from_slice(&z.to_repr());
+
+        let parsed =
+            Signature::try_from(signature.as_slice()).map_err(|_| FrostError::InvalidSignature)?;
+
+        self.public_key
+            .verify_prehash(message.as_slice(), &parsed)
+            .map_err(|_| FrostError::InvalidSignature)?;
+
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::keccak256;
+    use k256::schnorr::signature::hazmat::PrehashVerifier;
+    use k256::schnorr::{Signature, VerifyingKey};
+
+    use crate::helpers::generate_code::generate_random;
+    use crate::services::frost::{xonly_secret, FrostSigner};
+    use crate::services::polynomial::Polynomial;
+
+    #[test]
+    fn test_frost_signer() {
+        let sss = Polynomial::new();
+
+        let (secret, public_key) =
+            xonly_secret(generate_random().as_slice()).expect("Invalid secret");
+
+        let (shares, commitments) = sss.generate_shares(&secret, 5, 3);
+        assert_eq!(commitments.x_only_public_key(), Some(public_key));
+
+        let signer = FrostSigner::new(&shares[2..5], &public_key).expect("Invalid shares");
+
+        let message = keccak256("Hello, world!");
+        let signature = signer.sign(&message).expect("Failed to sign");
+
+        VerifyingKey::from_bytes(&public_key)
+            .unwrap()
+            .verify_prehash(
+                message.as_slice(),
+                &Signature::try_from(signature.as_slice()).unwrap(),
+            )
+            .expect("Invalid signature");
+
+        let mut shares = shares[0..3].to_vec();
+        shares[0].y += 1u32;
+
+        let corrupted = FrostSigner::new(&shares, &public_key).expect("Invalid shares");
+        assert!(corrupted.sign(&message).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::RngCore;
+use thiserror::Error;
+use vaultrs::error::ClientError;
+use vaultrs::transit;
+use zeroize::Zeroizing;
+
+use crate::config::Config;
+use crate::services::vault::{Vault, VaultError};
+
+const LOCAL_PREFIX: &str = "local:";
+const TRANSIT_PREFIX: &str = "vault:";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum KekError {
+    #[error("Invalid key-encryption key configuration: {0}")]
+    Config(String),
+    #[error("Vault transit error: {0}")]
+    Transit(ClientError),
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+    #[error("Data key was wrapped by a different key-encryption key")]
+    Mismatch,
+    #[error("Wrapped data key is malformed")]
+    Encoding,
+    #[error("Data key could not be unwrapped")]
+    Unwrap,
+}
+
+/// Key-encryption key protecting the per-key data keys that encrypt local shares.
+pub enum Kek {
+    Local(ChaCha20Poly1305),
+    /// Key of the Vault transit engine.
+    Transit {
+        vault: Arc<Vault>,
+        mount: String,
+        name: String,
+    },
+}
+
+impl Kek {
+    pub fn from_config(
+        config: &Config,
+        vault: Option<Arc<Vault>>,
+    ) -> Result<Option<Self>, KekError> {
+        match (&config.local_kek, &config.vault_transit_key) {
+            (Some(_), Some(_)) => Err(KekError::Config(
+                "LOCAL_KEK and VAULT_TRANSIT_KEY are mutually exclusive".to_string(),
+            )),
+            (Some(local_kek), None) => Self::from_hex(local_kek).map(Some),
+            (None, Some(name)) => {
+                let vault = vault.ok_or_else(|| {
+                    KekError::Config("VAULT_TRANSIT_KEY requires VAULT_STORAGE".to_string())
+                })?;
+
+                Ok(Some(Kek::Transit {
+                    vault,
+                    mount: config
+                        .vault_transit_mount
+                        .clone()
+                        .unwrap_or_else(|| "transit".to_string()),
+                    name: name.clone(),
+                }))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn from_hex(local_kek: &str) -> Result<Self, KekError> {
+        let key = hex::decode(local_kek.trim_start_matches("0x"))
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                KekError::Config("LOCAL_KEK must be 32 hex encoded bytes".to_string())
+            })?;
+
+        Ok(Kek::Local(ChaCha20Poly1305::new(Key::from_slice(&key))))
+    }
+
+    pub async fn wrap(&self, dek: &[u8; 32]) -> Result<String, KekError> {
+        match self {
+            Kek::Local(cipher) => {
+                let mut nonce = [0u8; NONCE_LEN];
+                rand::thread_rng().fill_bytes(&mut nonce);
+
+                let ciphertext = cipher
+                    .encrypt(Nonce::from_slice(&nonce), dek.as_slice())
+                    .map_err(|_| KekError::Encoding)?;
+
+                Ok(format!(
+                    "{LOCAL_PREFIX}{}",
+                    STANDARD.encode([nonce.as_slice(), &ciphertext].concat())
+                ))
+            }
+            Kek::Transit { vault, mount, name } => {
+                let response = transit::data::encrypt(
+                    vault.client()?.as_ref(),
+                    mount,
+                    name,
+                    &STANDARD.encode(dek),
+                    None,
+                )
+                .await
+                .map_err(KekError::Transit)?;
+
+                Ok(response.ciphertext)
+            }
+        }
+    }
+
+    pub async fn unwrap(&self, wrapped: &str) -> Result<Zeroizing<[u8; 32]>, KekError> {
+        let dek = Zeroizing::new(match self {
+            Kek::Local(cipher) => {
+                let encoded = wrapped
+                    .strip_prefix(LOCAL_PREFIX)
+                    .ok_or(KekError::Mismatch)?;
+                let payload = STANDARD.decode(encoded).map_err(|_| KekError::Encoding)?;
+
+                if payload.len() <= NONCE_LEN {
+                    return Err(KekError::Encoding);
+                }
+
+                let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+                cipher
+                    .decrypt(Nonce::from_slice(nonce), ciphertext)
+                    .map_err(|_| KekError::Unwrap)?
+            }
+            Kek::Transit { vault, mount, name } => {
+                if !wrapped.starts_with(TRANSIT_PREFIX) {
+                    return Err(KekError::Mismatch);
+                }
+
+                let response =
+                    transit::data::decrypt(vault.client()?.as_ref(), mount, name, wrapped, None)
+                        .await
+                        .map_err(KekError::Transit)?;
+
+                STANDARD
+                    .decode(Zeroizing::new(response.plaintext).as_bytes())
+                    .map_err(|_| KekError::Encoding)?
+            }
+        });
+
+        <[u8; 32]>::try_from(dek.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| KekError::Encoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::kek::{Kek, KekError};
+
+    #[tokio::test]
+    async fn test_local_kek() {
+        let kek = Kek::from_hex(&"11".repeat(32)).unwrap();
+        let other = Kek::from_hex(&"22".repeat(32)).unwrap();
+
+        let dek = [7u8; 32];
+        let wrapped = kek.wrap(&dek).await.unwrap();
+
+        assert_eq!(*kek.unwrap(&wrapped).await.unwrap(), dek);
+        assert!(matches!(
+            other.unwrap(&wrapped).await,
+            Err(KekError::Unwrap)
+        ));
+        assert!(matches!(
+            kek.unwrap("vault:v1:abcd").await,
+            Err(KekError::Mismatch)
+        ));
+        assert!(Kek::from_hex("1234").is_err());
+    }
+}
@@ -1 +1,7 @@
+pub mod additive;
+pub mod frost;
+pub mod kek;
 pub mod polynomial;
+pub mod signer_cache;
+pub mod storage;
+pub mod vault;
@@ -1,3 +1,10 @@
+use alloy::primitives::Address;
+use k256::ecdsa::VerifyingKey;
+use k256::elliptic_curve::group::Group;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, U256};
 use lazy_static::lazy_static;
 use num_bigint::BigUint;
 use num_traits::Zero;
@@ -44,6 +51,118 @@ impl From<&Share> for ShareStore {
     }
 }
 
+/// Feldman commitments `a_j * G` to the coefficients of a sharing polynomial.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commitments(pub Vec<ProjectivePoint>);
+
+pub(crate) fn to_scalar(value: &BigUint) -> Scalar {
+    let bytes = value.to_bytes_be();
+    let mut repr = FieldBytes::default();
+    repr[32 - bytes.len()..].copy_from_slice(&bytes);
+    <Scalar as Reduce<U256>>::reduce_bytes(&repr)
+}
+
+/// Lagrange-weights each share at zero, turning a threshold sharing into an additive one.
+/// Returns `None` if two shares have the same index. Allocated once, so callers can wrap
+/// the result in `Zeroizing` without leaving copies behind.
+pub(crate) fn additive_shares(shares: &[Share]) -> Option<Vec<Scalar>> {
+    let indexes = shares
+        .iter()
+        .map(|share| to_scalar(&share.x))
+        .collect::<Vec<_>>();
+
+    let mut secrets = Vec::with_capacity(shares.len());
+
+    for (i, share) in shares.iter().enumerate() {
+        let mut lambda = Scalar::ONE;
+
+        for (j, x_j) in indexes.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+
+            let denominator = Option::<Scalar>::from((x_j - &indexes[i]).invert())?;
+
+            lambda *= x_j * &denominator;
+        }
+
+        secrets.push(lambda * to_scalar(&share.y));
+    }
+
+    Some(secrets)
+}
+
+impl Commitments {
+    /// Checks `y * G == sum(C_j * x^j)` without touching any other share.
+    pub fn verify(&self, share: &Share) -> bool {
+        let x = to_scalar(&share.x);
+        let mut power = Scalar::ONE;
+        let mut expected = ProjectivePoint::IDENTITY;
+
+        for commitment in &self.0 {
+            expected += commitment * &power;
+            power *= &x;
+        }
+
+        ProjectivePoint::GENERATOR * to_scalar(&share.y) == expected
+    }
+
+    pub fn address(&self) -> Option<Address> {
+        let public_key = self.0.first()?;
+
+        if bool::from(public_key.is_identity()) {
+            return None;
+        }
+
+        VerifyingKey::from_affine(public_key.to_affine())
+            .ok()
+            .map(|key| Address::from_public_key(&key))
+    }
+
+    /// BIP-340 public key, only defined when the committed key has an even y coordinate.
+    pub fn x_only_public_key(&self) -> Option<[u8; 32]> {
+        let public_key = self.0.first()?;
+
+        if bool::from(public_key.is_identity()) {
+            return None;
+        }
+
+        let public_key = public_key.to_affine();
+
+        if bool::from(public_key.y_is_odd()) {
+            return None;
+        }
+
+        Some(public_key.x().into())
+    }
+
+    pub fn to_hex(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|point| hex::encode(point.to_affine().to_encoded_point(true).as_bytes()))
+            .collect()
+    }
+
+    pub fn from_hex(commitments: &[String]) -> Option<Self> {
+        commitments
+            .iter()
+            .map(|commitment| {
+                let bytes = hex::decode(commitment).ok()?;
+                let point = EncodedPoint::from_bytes(bytes).ok()?;
+                Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&point))
+                    .map(ProjectivePoint::from)
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Commitments)
+    }
+
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let commitments = serde_json::from_value::<Vec<String>>(value.clone()).ok()?;
+
+        Self::from_hex(&commitments)
+    }
+}
+
 pub struct Polynomial {
     prime: BigUint,
 }
@@ -78,12 +197,21 @@ impl Polynomial {
         result
     }
 
+    fn commit(&self, coefficients: &[BigUint]) -> Commitments {
+        Commitments(
+            coefficients
+                .iter()
+                .map(|coeff| ProjectivePoint::GENERATOR * to_scalar(coeff))
+                .collect(),
+        )
+    }
+
     pub fn generate_shares(
         &self,
         secret: &BigUint,
         num_shares: usize,
         threshold: usize,
-    ) -> Vec<Share> {
+    ) -> (Vec<Share>, Commitments) {
         let coefficients = self.random_polynomial(threshold - 1, secret);
         let mut shares = vec![];
         for _x in 1..=num_shares {
@@ -91,7 +219,7 @@ impl Polynomial {
             let y = self.evaluate_polynomial(&coefficients, &x);
             shares.push(Share { x, y });
         }
-        shares
+        (shares, self.commit(&coefficients))
     }
 
     pub fn reconstruct_secret(&self, shares: &Vec<Share>) -> BigUint {
@@ -117,8 +245,104 @@ impl Polynomial {
         secret
     }
 
-    pub fn add_share(&self, shares: &Vec<Share>) -> Share {
-        let new_index = BigUint::from_bytes_be(generate_random().as_slice());
+    /// Re-randomizes shares by adding a random polynomial with a zero constant
+    /// term, so the secret is unchanged but old shares no longer combine with new ones.
+    pub fn refresh_shares(&self, shares: &[Share], threshold: usize) -> Vec<Share> {
+        self.refresh_with_coefficients(shares, threshold).0
+    }
+
+    /// Same as [`Polynomial::refresh_shares`], but also moves the commitments onto
+    /// the refreshed polynomial.
+    pub fn refresh_verifiable_shares(
+        &self,
+        shares: &[Share],
+        commitments: &Commitments,
+    ) -> (Vec<Share>, Commitments) {
+        let (shares, coefficients) = self.refresh_with_coefficients(shares, commitments.0.len());
+
+        let delta = self.commit(&coefficients);
+        let commitments = commitments
+            .0
+            .iter()
+            .zip(delta.0)
+            .map(|(commitment, delta)| commitment + &delta)
+            .collect();
+
+        (shares, Commitments(commitments))
+    }
+
+    fn refresh_with_coefficients(
+        &self,
+        shares: &[Share],
+        threshold: usize,
+    ) -> (Vec<Share>, Vec<BigUint>) {
+        let coefficients = self.random_polynomial(threshold - 1, &BigUint::zero());
+
+        let shares = shares
+            .iter()
+            .map(|share| Share {
+                x: share.x.clone(),
+                y: (&share.y + self.evaluate_polynomial(&coefficients, &share.x)) % &self.prime,
+            })
+            .collect();
+
+        (shares, coefficients)
+    }
+
+    /// Moves a sharing to a new `threshold` and `num_shares` without summing the secret:
+    /// every old share holder splits its Lagrange-weighted share with a fresh polynomial and
+    /// the new shares (and commitments) are the sums of those sub-shares.
+    pub fn reshare(
+        &self,
+        shares: &[Share],
+        num_shares: usize,
+        threshold: usize,
+    ) -> Option<(Vec<Share>, Commitments)> {
+        let sub_polynomials = additive_shares(shares)?
+            .iter()
+            .map(|secret| {
+                self.random_polynomial(threshold - 1, &BigUint::from_bytes_be(&secret.to_bytes()))
+            })
+            .collect::<Vec<_>>();
+
+        let commitments = sub_polynomials
+            .iter()
+            .map(|coefficients| self.commit(coefficients))
+            .fold(
+                Commitments(vec![ProjectivePoint::IDENTITY; threshold]),
+                |acc, commitments| {
+                    Commitments(
+                        acc.0
+                            .iter()
+                            .zip(commitments.0)
+                            .map(|(acc, commitment)| acc + &commitment)
+                            .collect(),
+                    )
+                },
+            );
+
+        let shares = (0..num_shares)
+            .map(|_| {
+                let x = BigUint::from_bytes_be(generate_random().as_slice());
+                let y = sub_polynomials
+                    .iter()
+                    .fold(BigUint::zero(), |acc, coefficients| {
+                        (acc + self.evaluate_polynomial(coefficients, &x)) % &self.prime
+                    });
+                Share { x, y }
+            })
+            .collect();
+
+        Some((shares, commitments))
+    }
+
+    pub fn add_share(&self, shares: &[Share]) -> Share {
+        self.share_at(shares, BigUint::from_bytes_be(generate_random().as_slice()))
+    }
+
+    /// Evaluates the polynomial through `shares` at `new_index`, e.g. to rebuild a share
+    /// that is not at hand from a threshold of others.
+    pub fn share_at(&self, shares: &[Share], new_index: BigUint) -> Share {
         let mut result = BigUint::zero();
 
         for share_i in shares {
@@ -156,7 +380,7 @@ mod tests {
     use num_bigint::BigUint;
     use num_traits::Num;
 
-    use crate::services::polynomial::Polynomial;
+    use crate::services::polynomial::{Commitments, Polynomial};
 
     #[test]
     fn test_polynomial() {
@@ -172,14 +396,10 @@ mod tests {
 
         println!("Original secret: {}", secret);
 
-        let shares = sss.generate_shares(&secret, num_shares, threshold);
+        let (shares, _) = sss.generate_shares(&secret, num_shares, threshold);
         println!("Generated shares: {:?}", shares);
 
-        let mut subset_shares = vec![];
-
-        for i in 0..threshold {
-            subset_shares.push(shares[i].clone());
-        }
+        let subset_shares = shares[0..threshold].to_vec();
 
         let reconstructed_secret = sss.reconstruct_secret(&subset_shares.to_vec());
         println!("Reconstructed secret: {}", reconstructed_secret);
@@ -197,4 +417,92 @@ mod tests {
 
         assert_eq!(secret, reconstructed_secret);
     }
+
+    #[test]
+    fn test_refresh_shares() {
+        let sss = Polynomial::new();
+
+        let secret = BigUint::from_str_radix(
+            "9c22ff5f21f0b81b113e63f7db6da94fedef11b2119b4088b89664fb9a3cb658",
+            16,
+        )
+        .expect("Invalid secret");
+
+        let (shares, _) = sss.generate_shares(&secret, 4, 3);
+        let refreshed = sss.refresh_shares(&shares, 3);
+
+        assert_eq!(secret, sss.reconstruct_secret(&refreshed[1..4].to_vec()));
+        assert_ne!(shares[0].y, refreshed[0].y);
+
+        let mixed = vec![
+            refreshed[0].clone(),
+            refreshed[1].clone(),
+            shares[2].clone(),
+        ];
+
+        assert_ne!(secret, sss.reconstruct_secret(&mixed));
+
+        let rebuilt = sss.share_at(&shares[0..3], shares[3].x.clone());
+        assert_eq!(rebuilt.y, shares[3].y);
+    }
+
+    #[test]
+    fn test_commitments() {
+        let sss = Polynomial::new();
+
+        let secret = BigUint::from_str_radix(
+            "9c22ff5f21f0b81b113e63f7db6da94fedef11b2119b4088b89664fb9a3cb658",
+            16,
+        )
+        .expect("Invalid secret");
+
+        let (shares, commitments) = sss.generate_shares(&secret, 4, 3);
+
+        assert!(shares.iter().all(|share| commitments.verify(share)));
+        assert_eq!(
+            Commitments::from_hex(&commitments.to_hex()),
+            Some(commitments.clone())
+        );
+
+        let new_share = sss.add_share(&shares[0..3]);
+        assert!(commitments.verify(&new_share));
+
+        let mut corrupted = shares[1].clone();
+        corrupted.y += 1u32;
+        assert!(!commitments.verify(&corrupted));
+
+        let (refreshed, refreshed_commitments) =
+            sss.refresh_verifiable_shares(&shares, &commitments);
+
+        assert!(refreshed
+            .iter()
+            .all(|share| refreshed_commitments.verify(share)));
+        assert!(!refreshed_commitments.verify(&shares[0]));
+        assert_eq!(commitments.address(), refreshed_commitments.address());
+    }
+
+    #[test]
+    fn test_reshare() {
+        let sss = Polynomial::new();
+
+        let secret = BigUint::from_str_radix(
+            "9c22ff5f21f0b81b113e63f7db6da94fedef11b2119b4088b89664fb9a3cb658",
+            16,
+        )
+        .expect("Invalid secret");
+
+        let (shares, commitments) = sss.generate_shares(&secret, 3, 2);
+        let (reshared, reshared_commitments) =
+            sss.reshare(&shares[0..2], 5, 4).expect("Invalid shares");
+
+        assert_eq!(reshared.len(), 5);
+        assert_eq!(reshared_commitments.0.len(), 4);
+        assert_eq!(commitments.address(), reshared_commitments.address());
+        assert!(reshared
+            .iter()
+            .all(|share| reshared_commitments.verify(share)));
+
+        assert_eq!(secret, sss.reconstruct_secret(&reshared[1..5].to_vec()));
+        assert_ne!(secret, sss.reconstruct_secret(&reshared[1..4].to_vec()));
+    }
 }
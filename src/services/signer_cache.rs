@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use alloy::primitives::B256;
+use alloy::signers::local::PrivateKeySigner;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::config::Config;
+use crate::constants::DEFAULT_SIGNER_CACHE_MAX_USES;
+use crate::models::keys::Model as KeyModel;
+
+struct CachedSigner {
+    private_key: Zeroizing<[u8; 32]>,
+    key: KeyModel,
+    share_ids: Vec<Uuid>,
+    expires: Instant,
+    uses: u32,
+}
+
+/// Reconstructed private keys, cached per share token for `signer_cache_ttl` seconds and
+/// `signer_cache_max_uses` signatures, so repeated signing skips restoring the shares.
+pub struct SignerCache {
+    ttl: Duration,
+    max_uses: u32,
+    signers: Mutex<HashMap<[u8; 32], CachedSigner>>,
+    /// Bumped by every invalidation, signers restored before it are not cached.
+    epoch: AtomicU64,
+}
+
+impl SignerCache {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let ttl = config.signer_cache_ttl.filter(|ttl| *ttl > 0)?;
+
+        Some(SignerCache {
+            ttl: Duration::from_secs(ttl),
+            max_uses: config
+                .signer_cache_max_uses
+                .unwrap_or(DEFAULT_SIGNER_CACHE_MAX_USES),
+            signers: Mutex::new(HashMap::new()),
+            epoch: AtomicU64::new(0),
+        })
+    }
+
+    /// Cache entry of the `x-secret-key` and `x-passphrase` headers, the shares themselves are
+    /// never kept.
+    pub fn credentials(secret_key: &str, passphrase: Option<&str>) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(secret_key)
+            .chain_update([0])
+            .chain_update(passphrase.unwrap_or_default())
+            .finalize()
+            .into()
+    }
+
+    /// Read before restoring the shares of a signer that is then passed to
+    /// [`SignerCache::insert`].
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    pub fn get(&self, credentials: &[u8; 32]) -> Option<(PrivateKeySigner, KeyModel, Vec<Uuid>)> {
+        let mut signers = self.signers.lock().expect("Signer cache lock poisoned");
+
+        let cached = signers.get_mut(credentials)?;
+
+        if cached.expires <= Instant::now() || cached.uses >= self.max_uses {
+            signers.remove(credentials);
+            return None;
+        }
+
+        cached.uses += 1;
+
+        let signer = PrivateKeySigner::from_bytes(&B256::from(*cached.private_key)).ok()?;
+
+        Some((signer, cached.key.clone(), cached.share_ids.clone()))
+    }
+
+    pub fn insert(
+        &self,
+        credentials: [u8; 32],
+        epoch: u64,
+        private_key: Zeroizing<[u8; 32]>,
+        key: KeyModel,
+        share_ids: Vec<Uuid>,
+    ) {
+        let mut signers = self.signers.lock().expect("Signer cache lock poisoned");
+
+        // A share was revoked while this signer was restored.
+        if epoch != self.epoch() {
+            return;
+        }
+
+        let now = Instant::now();
+        signers.retain(|_, cached| cached.expires > now);
+
+        signers.insert(
+            credentials,
+            CachedSigner {
+                private_key,
+                key,
+                share_ids,
+                expires: now + self.ttl,
+                uses: 1,
+            },
+        );
+    }
+
+    /// Drops every signer restored with the share.
+    pub fn invalidate_share(&self, share_id: &Uuid) {
+        self.invalidate(|cached| cached.share_ids.contains(share_id));
+    }
+
+    /// Drops every signer of the key, when its shares are replaced.
+    pub fn invalidate_key(&self, key_id: &Uuid) {
+        self.invalidate(|cached| cached.key.id == *key_id);
+    }
+
+    fn invalidate(&self, matches: impl Fn(&CachedSigner) -> bool) {
+        let mut signers = self.signers.lock().expect("Signer cache lock poisoned");
+
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        signers.retain(|_, cached| !matches(cached));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use zeroize::Zeroizing;
+
+    use crate::config::Config;
+    use crate::models::keys::{KeyType, Model as KeyModel};
+    use crate::services::signer_cache::SignerCache;
+
+    fn key() -> KeyModel {
+        KeyModel {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            local_key: String::new(),
+            local_index: String::new(),
+            local_dek: None,
+            cloud_key: String::new(),
+            cloud_version: None,
+            address: String::new(),
+            allow_raw_hash: false,
+            allow_signer_cache: true,
+            total_shares: 3,
+            threshold: 3,
+            commitments: None,
+            key_type: KeyType::Ecdsa,
+            updated_at: Utc::now().into(),
+            created_at: Utc::now().into(),
+        }
+    }
+
+    fn cache(max_uses: u32) -> SignerCache {
+        let mut config = Config::in_memory();
+        config.signer_cache_ttl = Some(60);
+        config.signer_cache_max_uses = Some(max_uses);
+
+        SignerCache::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_signer_cache() {
+        assert!(SignerCache::from_config(&Config::in_memory()).is_none());
+
+        let cache = cache(3);
+        let key = key();
+        let share_id = Uuid::new_v4();
+        let credentials = SignerCache::credentials("share", None);
+
+        assert_ne!(
+            credentials,
+            SignerCache::credentials("share", Some("passphrase"))
+        );
+
+        cache.insert(
+            credentials,
+            cache.epoch(),
+            Zeroizing::new([1; 32]),
+            key.clone(),
+            vec![share_id],
+        );
+
+        // The inserting request counts as the first use.
+        assert!(cache.get(&credentials).is_some());
+        let (_signer, cached, share_ids) = cache.get(&credentials).unwrap();
+        assert_eq!((cached.id, share_ids), (key.id, vec![share_id]));
+        assert!(cache.get(&credentials).is_none());
+
+        cache.insert(
+            credentials,
+            cache.epoch(),
+            Zeroizing::new([1; 32]),
+            key.clone(),
+            vec![share_id],
+        );
+        cache.invalidate_share(&Uuid::new_v4());
+        assert!(cache.get(&credentials).is_some());
+        cache.invalidate_share(&share_id);
+        assert!(cache.get(&credentials).is_none());
+
+        // Restored before the key's shares were replaced, never cached.
+        let epoch = cache.epoch();
+        cache.invalidate_key(&key.id);
+        cache.insert(
+            credentials,
+            epoch,
+            Zeroizing::new([1; 32]),
+            key,
+            vec![share_id],
+        );
+        assert!(cache.get(&credentials).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::RngCore;
+use tokio::fs;
+
+use crate::services::polynomial::ShareStore;
+use crate::services::storage::{ShareStorage, StorageError};
+
+const NONCE_LEN: usize = 12;
+
+/// One encrypted file per share in a local directory, for deployments without Vault.
+pub struct FileStorage {
+    dir: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl FileStorage {
+    pub fn new(dir: &str, key: &str) -> Result<Self, StorageError> {
+        let key = hex::decode(key.trim_start_matches("0x"))
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                StorageError::Config("SHARE_STORAGE_KEY must be 32 hex encoded bytes".to_string())
+            })?;
+
+        std::fs::create_dir_all(dir)?;
+
+        Ok(FileStorage {
+            dir: PathBuf::from(dir),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        })
+    }
+
+    /// Segments of the path are directories, each restricted to `[A-Za-z0-9_-]`.
+    fn file(&self, path: &str) -> Result<PathBuf, StorageError> {
+        let valid = path.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+
+        if !valid {
+            return Err(StorageError::Config(format!("Invalid share path {path}")));
+        }
+
+        Ok(self.dir.join(format!("{path}.share")))
+    }
+}
+
+fn not_found(path: &str) -> impl Fn(std::io::Error) -> StorageError + '_ {
+    move |err| match err.kind() {
+        ErrorKind::NotFound => StorageError::NotFound(path.to_string()),
+        _ => StorageError::Io(err),
+    }
+}
+
+#[async_trait]
+impl ShareStorage for FileStorage {
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError> {
+        let file = self.file(path)?;
+        let plaintext = serde_json::to_vec(share).map_err(|_| StorageError::Encoding)?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        // The path is authenticated, so share files can not be swapped between keys.
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map_err(|_| StorageError::Encoding)?;
+
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Written next to the target and linked, so a crash never leaves a partial share and
+        // an existing share is never replaced.
+        let temporary = file.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&temporary, [nonce.as_slice(), &ciphertext].concat()).await?;
+
+        let linked = fs::hard_link(&temporary, &file).await;
+        fs::remove_file(&temporary).await?;
+
+        match linked {
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                Err(StorageError::Collision(path.to_string()))
+            }
+            linked => linked.map(|_| None).map_err(StorageError::Io),
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError> {
+        let payload = fs::read(self.file(path)?).await.map_err(not_found(path))?;
+
+        if payload.len() <= NONCE_LEN {
+            return Err(StorageError::Encoding);
+        }
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: path.as_bytes(),
+                },
+            )
+            .map_err(|_| StorageError::Encoding)?;
+
+        serde_json::from_slice(&plaintext).map_err(|_| StorageError::Encoding)
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.file(path)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(StorageError::Io(err)),
+            _ => Ok(()),
+        }
+    }
+}
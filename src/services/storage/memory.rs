@@ -0,0 +1,96 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+
+use crate::services::polynomial::ShareStore;
+use crate::services::storage::{ShareStorage, StorageError};
+
+/// Process local shares, lost on restart. Meant for tests and local development. Versions
+/// are kept like in Vault KV2, numbered from 1, `None` once destroyed.
+#[derive(Default)]
+pub struct MemoryStorage {
+    shares: RwLock<HashMap<String, Vec<Option<ShareStore>>>>,
+}
+
+#[async_trait]
+impl ShareStorage for MemoryStorage {
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError> {
+        match self
+            .shares
+            .write()
+            .expect("Share storage lock poisoned")
+            .entry(path.to_string())
+        {
+            Entry::Occupied(_) => Err(StorageError::Collision(path.to_string())),
+            Entry::Vacant(entry) => {
+                entry.insert(vec![Some(share.clone())]);
+                Ok(Some(1))
+            }
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError> {
+        self.shares
+            .read()
+            .expect("Share storage lock poisoned")
+            .get(path)
+            .and_then(|versions| versions.last())
+            .cloned()
+            .flatten()
+            .ok_or_else(|| StorageError::NotFound(path.to_string()))
+    }
+
+    fn versioned(&self) -> bool {
+        true
+    }
+
+    async fn update(&self, path: &str, share: &ShareStore) -> Result<u64, StorageError> {
+        let mut shares = self.shares.write().expect("Share storage lock poisoned");
+
+        let versions = shares
+            .get_mut(path)
+            .ok_or_else(|| StorageError::NotFound(path.to_string()))?;
+        versions.push(Some(share.clone()));
+
+        Ok(versions.len() as u64)
+    }
+
+    async fn get_version(&self, path: &str, version: u64) -> Result<ShareStore, StorageError> {
+        self.shares
+            .read()
+            .expect("Share storage lock poisoned")
+            .get(path)
+            .and_then(|versions| versions.get((version as usize).checked_sub(1)?))
+            .cloned()
+            .flatten()
+            .ok_or_else(|| StorageError::NotFound(format!("{path} version {version}")))
+    }
+
+    async fn destroy_versions(&self, path: &str, versions: &[u64]) -> Result<(), StorageError> {
+        let mut shares = self.shares.write().expect("Share storage lock poisoned");
+
+        if let Some(stored) = shares.get_mut(path) {
+            for version in versions {
+                if let Some(share) = (*version as usize)
+                    .checked_sub(1)
+                    .and_then(|index| stored.get_mut(index))
+                {
+                    *share = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), StorageError> {
+        self.shares
+            .write()
+            .expect("Share storage lock poisoned")
+            .remove(path);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sea_orm::{DatabaseConnection, DbErr};
+use thiserror::Error;
+use vaultrs::error::ClientError;
+
+use crate::config::{Config, ShareStorageKind};
+use crate::models::keys::Model as KeyModel;
+use crate::services::polynomial::ShareStore;
+use crate::services::vault::{Vault, VaultError};
+
+pub use file::FileStorage;
+pub use memory::MemoryStorage;
+pub use pkcs11::Pkcs11Storage;
+pub use transit_postgres::TransitPostgresStorage;
+pub use vault::VaultStorage;
+
+pub mod file;
+pub mod memory;
+pub mod pkcs11;
+pub mod transit_postgres;
+pub mod vault;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Share {0} not found")]
+    NotFound(String),
+    #[error("Share path {0} is already in use")]
+    Collision(String),
+    #[error("Vault error: {0}")]
+    Vault(ClientError),
+    #[error(transparent)]
+    VaultAuth(#[from] VaultError),
+    #[error("Database error: {0}")]
+    DbErr(DbErr),
+    #[error("PKCS#11 error: {0}")]
+    Pkcs11(#[from] cryptoki::error::Error),
+    #[error("File error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Stored share is malformed")]
+    Encoding,
+    #[error("Invalid share storage configuration: {0}")]
+    Config(String),
+    #[error("Share storage does not keep versions")]
+    Unversioned,
+}
+
+/// Storage of the cloud share of every key, addressed by the key's `cloud_key` path.
+#[async_trait]
+pub trait ShareStorage: Send + Sync {
+    /// Stores a new share, [`StorageError::Collision`] if `path` is already in use. Returns
+    /// the version of the share on versioned storages.
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError>;
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError>;
+
+    /// Whether shares can be rotated in place with [`ShareStorage::update`], keeping earlier
+    /// versions readable with [`ShareStorage::get_version`].
+    fn versioned(&self) -> bool {
+        false
+    }
+
+    /// Writes a new version of the share at `path` and returns its version.
+    async fn update(&self, _path: &str, _share: &ShareStore) -> Result<u64, StorageError> {
+        Err(StorageError::Unversioned)
+    }
+
+    async fn get_version(&self, _path: &str, _version: u64) -> Result<ShareStore, StorageError> {
+        Err(StorageError::Unversioned)
+    }
+
+    /// Permanently removes earlier versions of the share at `path`, versions that no longer
+    /// exist are skipped.
+    async fn destroy_versions(&self, _path: &str, _versions: &[u64]) -> Result<(), StorageError> {
+        Err(StorageError::Unversioned)
+    }
+
+    /// Removes the share at `path`, every version of it where the backend keeps history.
+    async fn delete(&self, path: &str) -> Result<(), StorageError>;
+}
+
+/// The cloud share of `key`, at the version recorded for it on versioned storages, so a
+/// rotation that was written but never committed is not picked up.
+pub async fn read_cloud_share(
+    storage: &dyn ShareStorage,
+    key: &KeyModel,
+) -> Result<ShareStore, StorageError> {
+    match key.cloud_version {
+        Some(version) => storage.get_version(&key.cloud_key, version as u64).await,
+        None => storage.get(&key.cloud_key).await,
+    }
+}
+
+pub fn from_config(
+    config: &Config,
+    vault: Option<Arc<Vault>>,
+    db: &DatabaseConnection,
+) -> Result<Arc<dyn ShareStorage>, StorageError> {
+    let require_vault = || {
+        vault.clone().ok_or_else(|| {
+            StorageError::Config("VAULT_STORAGE is required for this share storage".to_string())
+        })
+    };
+
+    Ok(match config.share_storage {
+        ShareStorageKind::Vault => Arc::new(VaultStorage::new(
+            require_vault()?,
+            config.vault_mount.as_deref().unwrap_or("secret"),
+        )),
+        ShareStorageKind::TransitPostgres => {
+            let key = config.share_storage_transit_key.clone().ok_or_else(|| {
+                StorageError::Config("SHARE_STORAGE_TRANSIT_KEY is required".to_string())
+            })?;
+
+            Arc::new(TransitPostgresStorage::new(
+                require_vault()?,
+                config.vault_transit_mount.as_deref().unwrap_or("transit"),
+                key,
+                db.clone(),
+            ))
+        }
+        ShareStorageKind::File => {
+            let (Some(dir), Some(key)) = (&config.share_storage_dir, &config.share_storage_key)
+            else {
+                return Err(StorageError::Config(
+                    "SHARE_STORAGE_DIR and SHARE_STORAGE_KEY are required".to_string(),
+                ));
+            };
+
+            Arc::new(FileStorage::new(dir, key)?)
+        }
+        ShareStorageKind::Memory => Arc::new(MemoryStorage::default()),
+        ShareStorageKind::Pkcs11 => {
+            let (Some(module), Some(token), Some(pin)) = (
+                &config.pkcs11_module,
+                &config.pkcs11_token,
+                &config.pkcs11_pin,
+            ) else {
+                return Err(StorageError::Config(
+                    "PKCS11_MODULE, PKCS11_TOKEN and PKCS11_PIN are required".to_string(),
+                ));
+            };
+
+            Arc::new(Pkcs11Storage::new(
+                module,
+                token,
+                pin,
+                config
+                    .pkcs11_key
+                    .as_deref()
+                    .unwrap_or(pkcs11::DEFAULT_KEY_LABEL),
+                db.clone(),
+            )?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::services::polynomial::ShareStore;
+    use crate::services::storage::{
+        FileStorage, MemoryStorage, ShareStorage, StorageError, VaultStorage,
+    };
+    use crate::services::vault::Vault;
+
+    fn share(y: &str) -> ShareStore {
+        ShareStore {
+            x: "01".to_string(),
+            y: y.to_string(),
+        }
+    }
+
+    /// Behaviour every backend has to share, the versioned part only on versioned storages.
+    /// Paths are unique per call, so backends that outlive the test can be checked as well.
+    async fn check_contract(storage: &dyn ShareStorage) {
+        let path = format!("kms-test/{}", uuid::Uuid::new_v4());
+        let (share, rotated) = (share("abcd"), share("ef01"));
+
+        let version = storage.set(&path, &share).await.unwrap();
+        assert_eq!(version, storage.versioned().then_some(1));
+        assert!(matches!(
+            storage.set(&path, &share).await,
+            Err(StorageError::Collision(_))
+        ));
+        assert_eq!(storage.get(&path).await.unwrap().y, share.y);
+
+        if storage.versioned() {
+            assert_eq!(storage.update(&path, &rotated).await.unwrap(), 2);
+            assert_eq!(storage.get(&path).await.unwrap().y, rotated.y);
+            assert_eq!(storage.get_version(&path, 1).await.unwrap().y, share.y);
+            assert!(matches!(
+                storage.get_version(&path, 3).await,
+                Err(StorageError::NotFound(_))
+            ));
+            assert!(matches!(
+                storage.update(&format!("{path}-missing"), &rotated).await,
+                Err(StorageError::NotFound(_))
+            ));
+
+            storage.destroy_versions(&path, &[1, 5]).await.unwrap();
+            assert!(matches!(
+                storage.get_version(&path, 1).await,
+                Err(StorageError::NotFound(_))
+            ));
+            assert_eq!(storage.get(&path).await.unwrap().y, rotated.y);
+        } else {
+            assert!(matches!(
+                storage.update(&path, &rotated).await,
+                Err(StorageError::Unversioned)
+            ));
+            assert!(matches!(
+                storage.get_version(&path, 1).await,
+                Err(StorageError::Unversioned)
+            ));
+            assert!(matches!(
+                storage.destroy_versions(&path, &[1]).await,
+                Err(StorageError::Unversioned)
+            ));
+        }
+
+        storage.delete(&path).await.unwrap();
+        assert!(matches!(
+            storage.get(&path).await,
+            Err(StorageError::NotFound(_))
+        ));
+        storage.delete(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_share_storage() {
+        check_contract(&MemoryStorage::default()).await;
+
+        let dir = std::env::temp_dir().join(format!("kms-shares-{}", uuid::Uuid::new_v4()));
+        let storage = FileStorage::new(dir.to_str().unwrap(), &"11".repeat(32)).unwrap();
+        check_contract(&storage).await;
+
+        assert!(matches!(
+            storage.get("../keys").await,
+            Err(StorageError::Config(_))
+        ));
+        assert!(FileStorage::new(dir.to_str().unwrap(), "1234").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Vault"]
+    async fn test_vault_share_storage() {
+        let config = Config::default();
+        let vault = Vault::connect(&config).await.unwrap();
+
+        check_contract(&VaultStorage::new(
+            vault,
+            config.vault_mount.as_deref().unwrap_or("secret"),
+        ))
+        .await;
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::mechanism::aead::GcmParams;
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use sea_orm::DatabaseConnection;
+use tracing::info;
+
+use crate::queries::cloud_shares::{create_cloud_share, delete_cloud_share, get_cloud_share};
+use crate::services::polynomial::ShareStore;
+use crate::services::storage::{ShareStorage, StorageError};
+
+pub const DEFAULT_KEY_LABEL: &str = "kms-cloud-share";
+const PREFIX: &str = "pkcs11:";
+const IV_LEN: usize = 12;
+const TAG_BITS: u64 = 128;
+
+/// Shares in the `cloud_shares` table, encrypted with AES-GCM by a non-extractable key of a
+/// PKCS#11 token, for deployments requiring hardware custody of the cloud share.
+///
+/// Shares are not versioned, so refreshes and reshares can not keep the previous sharing and
+/// `/keys/rollback` is unavailable with this storage.
+pub struct Pkcs11Storage {
+    token: Arc<Token>,
+    db: DatabaseConnection,
+}
+
+/// Token calls block until the module answers, they run on the blocking thread pool.
+struct Token {
+    /// Sessions are not `Sync`, a single logged in session is shared behind a lock.
+    session: Mutex<Session>,
+    key: ObjectHandle,
+}
+
+impl Pkcs11Storage {
+    pub fn new(
+        module: &str,
+        token: &str,
+        pin: &str,
+        label: &str,
+        db: DatabaseConnection,
+    ) -> Result<Self, StorageError> {
+        let pkcs11 = Pkcs11::new(module)?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+
+        let slot = pkcs11
+            .get_slots_with_token()?
+            .into_iter()
+            .find(|slot| {
+                pkcs11
+                    .get_token_info(*slot)
+                    .is_ok_and(|info| info.label() == token)
+            })
+            .ok_or_else(|| StorageError::Config(format!("PKCS#11 token {token} not found")))?;
+
+        let session = pkcs11.open_rw_session(slot)?;
+        session.login(UserType::User, Some(&AuthPin::new(pin.to_string())))?;
+
+        let template = [
+            Attribute::Class(ObjectClass::SECRET_KEY),
+            Attribute::KeyType(KeyType::AES),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ];
+
+        let key = match session.find_objects(&template)?.as_slice() {
+            [key] => *key,
+            [] => {
+                info!("Generating PKCS#11 share wrapping key {label}");
+
+                session.generate_key(
+                    &Mechanism::AesKeyGen,
+                    &[
+                        Attribute::Token(true),
+                        Attribute::Private(true),
+                        Attribute::Sensitive(true),
+                        Attribute::Extractable(false),
+                        Attribute::Encrypt(true),
+                        Attribute::Decrypt(true),
+                        Attribute::ValueLen(32.into()),
+                        Attribute::Label(label.as_bytes().to_vec()),
+                    ],
+                )?
+            }
+            _ => {
+                return Err(StorageError::Config(format!(
+                    "Multiple PKCS#11 keys labelled {label}"
+                )))
+            }
+        };
+
+        Ok(Pkcs11Storage {
+            token: Arc::new(Token {
+                session: Mutex::new(session),
+                key,
+            }),
+            db,
+        })
+    }
+
+    async fn encrypt(&self, path: &str, plaintext: Vec<u8>) -> Result<String, StorageError> {
+        let (token, path) = (self.token.clone(), path.to_string());
+
+        tokio::task::spawn_blocking(move || token.encrypt(&path, &plaintext))
+            .await
+            .map_err(std::io::Error::from)?
+    }
+
+    async fn decrypt(&self, path: &str, encrypted: String) -> Result<Vec<u8>, StorageError> {
+        let (token, path) = (self.token.clone(), path.to_string());
+
+        tokio::task::spawn_blocking(move || token.decrypt(&path, &encrypted))
+            .await
+            .map_err(std::io::Error::from)?
+    }
+}
+
+impl Token {
+    /// The path is authenticated, so ciphertexts can not be swapped between keys.
+    fn encrypt(&self, path: &str, plaintext: &[u8]) -> Result<String, StorageError> {
+        let session = self.session.lock().expect("PKCS#11 session lock poisoned");
+
+        // IVs come from the token, FIPS validated modules may refuse anything else.
+        let iv = session.generate_random_vec(IV_LEN as u32)?;
+        let ciphertext = session.encrypt(
+            &Mechanism::AesGcm(GcmParams::new(&iv, path.as_bytes(), TAG_BITS.into())),
+            self.key,
+            plaintext,
+        )?;
+
+        Ok(format!(
+            "{PREFIX}{}",
+            STANDARD.encode([iv.as_slice(), &ciphertext].concat())
+        ))
+    }
+
+    fn decrypt(&self, path: &str, encrypted: &str) -> Result<Vec<u8>, StorageError> {
+        let payload = encrypted
+            .strip_prefix(PREFIX)
+            .and_then(|encoded| STANDARD.decode(encoded).ok())
+            .filter(|payload| payload.len() > IV_LEN)
+            .ok_or(StorageError::Encoding)?;
+
+        let (iv, ciphertext) = payload.split_at(IV_LEN);
+
+        let session = self.session.lock().expect("PKCS#11 session lock poisoned");
+
+        Ok(session.decrypt(
+            &Mechanism::AesGcm(GcmParams::new(iv, path.as_bytes(), TAG_BITS.into())),
+            self.key,
+            ciphertext,
+        )?)
+    }
+}
+
+#[async_trait]
+impl ShareStorage for Pkcs11Storage {
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError> {
+        let plaintext = serde_json::to_vec(share).map_err(|_| StorageError::Encoding)?;
+        let ciphertext = self.encrypt(path, plaintext).await?;
+
+        create_cloud_share(path, ciphertext, &self.db).await?;
+
+        Ok(None)
+    }
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError> {
+        let share = get_cloud_share(path, &self.db).await?;
+        let plaintext = self.decrypt(path, share.ciphertext).await?;
+
+        serde_json::from_slice(&plaintext).map_err(|_| StorageError::Encoding)
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), StorageError> {
+        delete_cloud_share(path, &self.db).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sea_orm::DatabaseConnection;
+use vaultrs::transit;
+
+use crate::queries::cloud_shares::{
+    create_cloud_share, delete_cloud_share, get_cloud_share, CloudShareErrors,
+};
+use crate::services::polynomial::ShareStore;
+use crate::services::storage::{ShareStorage, StorageError};
+use crate::services::vault::Vault;
+
+/// Shares in the `cloud_shares` table, encrypted by a Vault transit key. Vault never stores
+/// the shares, so a database dump is useless without access to the transit key.
+pub struct TransitPostgresStorage {
+    vault: Arc<Vault>,
+    mount: String,
+    key: String,
+    db: DatabaseConnection,
+}
+
+impl TransitPostgresStorage {
+    pub fn new(vault: Arc<Vault>, mount: &str, key: String, db: DatabaseConnection) -> Self {
+        TransitPostgresStorage {
+            vault,
+            mount: mount.to_string(),
+            key,
+            db,
+        }
+    }
+}
+
+impl From<CloudShareErrors> for StorageError {
+    fn from(err: CloudShareErrors) -> Self {
+        match err {
+            CloudShareErrors::NotFound(path) => StorageError::NotFound(path),
+            CloudShareErrors::Exists(path) => StorageError::Collision(path),
+            CloudShareErrors::DbErr(err) => StorageError::DbErr(err),
+        }
+    }
+}
+
+#[async_trait]
+impl ShareStorage for TransitPostgresStorage {
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError> {
+        let plaintext = serde_json::to_vec(share).map_err(|_| StorageError::Encoding)?;
+
+        let encrypted = transit::data::encrypt(
+            self.vault.client()?.as_ref(),
+            &self.mount,
+            &self.key,
+            &STANDARD.encode(plaintext),
+            None,
+        )
+        .await
+        .map_err(StorageError::Vault)?;
+
+        create_cloud_share(path, encrypted.ciphertext, &self.db).await?;
+
+        Ok(None)
+    }
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError> {
+        let share = get_cloud_share(path, &self.db).await?;
+
+        let decrypted = transit::data::decrypt(
+            self.vault.client()?.as_ref(),
+            &self.mount,
+            &self.key,
+            &share.ciphertext,
+            None,
+        )
+        .await
+        .map_err(StorageError::Vault)?;
+
+        let plaintext = STANDARD
+            .decode(decrypted.plaintext)
+            .map_err(|_| StorageError::Encoding)?;
+
+        serde_json::from_slice(&plaintext).map_err(|_| StorageError::Encoding)
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), StorageError> {
+        delete_cloud_share(path, &self.db).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use vaultrs::api::kv2::requests::SetSecretRequestOptions;
+use vaultrs::error::ClientError;
+use vaultrs::kv2;
+
+use crate::services::polynomial::ShareStore;
+use crate::services::storage::{ShareStorage, StorageError};
+use crate::services::vault::Vault;
+
+/// Shares in a Vault KV2 secrets engine.
+pub struct VaultStorage {
+    vault: Arc<Vault>,
+    mount: String,
+}
+
+impl VaultStorage {
+    pub fn new(vault: Arc<Vault>, mount: &str) -> Self {
+        VaultStorage {
+            vault,
+            mount: mount.to_string(),
+        }
+    }
+
+    async fn write(&self, path: &str, share: &ShareStore, cas: u64) -> Result<u64, StorageError> {
+        kv2::set_with_options(
+            self.vault.client()?.as_ref(),
+            &self.mount,
+            path,
+            share,
+            SetSecretRequestOptions { cas: cas as u32 },
+        )
+        .await
+        .map(|metadata| metadata.version)
+        .map_err(|err| storage_error(path, err))
+    }
+}
+
+fn storage_error(path: &str, err: ClientError) -> StorageError {
+    match err {
+        ClientError::APIError { code: 404, .. } => StorageError::NotFound(path.to_string()),
+        ClientError::APIError { code: 400, errors }
+            if errors.iter().any(|error| error.contains("check-and-set")) =>
+        {
+            StorageError::Collision(path.to_string())
+        }
+        err => StorageError::Vault(err),
+    }
+}
+
+#[async_trait]
+impl ShareStorage for VaultStorage {
+    async fn set(&self, path: &str, share: &ShareStore) -> Result<Option<u64>, StorageError> {
+        // A check-and-set version of 0 only writes when the path holds no secret yet.
+        self.write(path, share, 0).await.map(Some)
+    }
+
+    async fn get(&self, path: &str) -> Result<ShareStore, StorageError> {
+        kv2::read(self.vault.client()?.as_ref(), &self.mount, path)
+            .await
+            .map_err(|err| storage_error(path, err))
+    }
+
+    fn versioned(&self) -> bool {
+        true
+    }
+
+    async fn update(&self, path: &str, share: &ShareStore) -> Result<u64, StorageError> {
+        let metadata = kv2::read_metadata(self.vault.client()?.as_ref(), &self.mount, path)
+            .await
+            .map_err(|err| storage_error(path, err))?;
+
+        // Two rotations of the same key race, only the first one is written.
+        self.write(path, share, metadata.current_version).await
+    }
+
+    async fn get_version(&self, path: &str, version: u64) -> Result<ShareStore, StorageError> {
+        kv2::read_version(self.vault.client()?.as_ref(), &self.mount, path, version)
+            .await
+            .map_err(|err| match storage_error(path, err) {
+                StorageError::NotFound(path) => {
+                    StorageError::NotFound(format!("{path} version {version}"))
+                }
+                err => err,
+            })
+    }
+
+    async fn destroy_versions(&self, path: &str, versions: &[u64]) -> Result<(), StorageError> {
+        kv2::destroy_versions(
+            self.vault.client()?.as_ref(),
+            &self.mount,
+            path,
+            versions.to_vec(),
+        )
+        .await
+        .map_err(|err| storage_error(path, err))
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), StorageError> {
+        kv2::delete_metadata(self.vault.client()?.as_ref(), &self.mount, path)
+            .await
+            .map_err(|err| storage_error(path, err))
+    }
+}
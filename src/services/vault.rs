@@ -0,0 +1,396 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{error, info, warn};
+use vaultrs::api::AuthInfo;
+use vaultrs::auth::{approle, kubernetes};
+use vaultrs::client::{VaultClient, VaultClientSettings, VaultClientSettingsBuilder};
+use vaultrs::error::ClientError;
+use vaultrs::token;
+
+use crate::config::{Config, VaultAuthMethod};
+
+pub const DEFAULT_KUBERNETES_TOKEN_PATH: &str =
+    "/var/run/secrets/kubernetes.io/serviceaccount/token";
+/// Retry interval after the first failed refresh, doubled after every further failure.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+const MIN_RENEWAL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Error)]
+pub enum VaultError {
+    #[error("Invalid Vault configuration: {0}")]
+    Config(String),
+    #[error("Vault login failed: {0}")]
+    Login(ClientError),
+    #[error("Vault token renewal failed: {0}")]
+    Renewal(String),
+    #[error("Vault token expired, renewal and login failed")]
+    Expired,
+}
+
+enum Credentials {
+    Token,
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: String,
+    },
+    Kubernetes {
+        mount: String,
+        role: String,
+        token_path: String,
+    },
+}
+
+struct Lease {
+    client: Arc<VaultClient>,
+    ttl: Duration,
+    renewable: bool,
+    /// `None` for tokens without a TTL, such as root tokens.
+    expires: Option<Instant>,
+    /// Refreshes failed in a row.
+    failures: u32,
+}
+
+/// Next step of the background renewal.
+enum Schedule {
+    Refresh(Duration),
+    /// The token can no longer be renewed nor replaced, report once it expires.
+    Expire(Duration),
+    Stop,
+}
+
+/// Vault client authenticated with the configured method. The token is renewed in the
+/// background, and replaced by a new login once it can no longer be renewed.
+pub struct Vault {
+    settings: VaultClientSettings,
+    credentials: Credentials,
+    lease: RwLock<Lease>,
+}
+
+#[derive(Deserialize)]
+struct RenewResponse {
+    auth: AuthInfo,
+}
+
+impl Vault {
+    pub async fn connect(config: &Config) -> Result<Arc<Self>, VaultError> {
+        let address = config.vault_storage.as_ref().ok_or_else(|| {
+            VaultError::Config("VAULT_STORAGE is required to connect to Vault".to_string())
+        })?;
+
+        let mut settings = VaultClientSettingsBuilder::default();
+        settings.address(address);
+
+        if let Some(token) = &config.vault_token {
+            settings.token(token);
+        }
+
+        if let Some(namespace) = &config.vault_namespace {
+            settings.set_namespace(namespace.clone());
+        }
+
+        let settings = settings
+            .build()
+            .map_err(|err| VaultError::Config(err.to_string()))?;
+
+        let mount = |default: &str| {
+            config
+                .vault_auth_mount
+                .clone()
+                .unwrap_or_else(|| default.to_string())
+        };
+
+        let credentials = match config.vault_auth {
+            VaultAuthMethod::Token => Credentials::Token,
+            VaultAuthMethod::AppRole => {
+                let (Some(role_id), Some(secret_id)) =
+                    (&config.vault_role_id, &config.vault_secret_id)
+                else {
+                    return Err(VaultError::Config(
+                        "VAULT_ROLE_ID and VAULT_SECRET_ID are required".to_string(),
+                    ));
+                };
+
+                Credentials::AppRole {
+                    mount: mount("approle"),
+                    role_id: role_id.clone(),
+                    secret_id: secret_id.clone(),
+                }
+            }
+            VaultAuthMethod::Kubernetes => Credentials::Kubernetes {
+                mount: mount("kubernetes"),
+                role: config.vault_kubernetes_role.clone().ok_or_else(|| {
+                    VaultError::Config("VAULT_KUBERNETES_ROLE is required".to_string())
+                })?,
+                token_path: config
+                    .vault_kubernetes_token_path
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_KUBERNETES_TOKEN_PATH.to_string()),
+            },
+        };
+
+        let lease = Self::login(&settings, &credentials).await?;
+
+        let vault = Arc::new(Vault {
+            settings,
+            credentials,
+            lease: RwLock::new(lease),
+        });
+
+        vault.spawn_renewal();
+
+        Ok(vault)
+    }
+
+    /// The current client, or [`VaultError::Expired`] once the token expired without being
+    /// renewed, rather than letting every request fail with a permission error.
+    pub fn client(&self) -> Result<Arc<VaultClient>, VaultError> {
+        let lease = self.lease.read().expect("Vault lease lock poisoned");
+
+        if lease
+            .expires
+            .is_some_and(|expires| expires <= Instant::now())
+        {
+            return Err(VaultError::Expired);
+        }
+
+        Ok(lease.client.clone())
+    }
+
+    fn client_with_token(
+        settings: &VaultClientSettings,
+        token: &str,
+    ) -> Result<VaultClient, VaultError> {
+        let mut settings = settings.clone();
+        settings.token = token.to_string();
+
+        VaultClient::new(settings).map_err(|err| VaultError::Config(err.to_string()))
+    }
+
+    async fn login(
+        settings: &VaultClientSettings,
+        credentials: &Credentials,
+    ) -> Result<Lease, VaultError> {
+        let client = Self::client_with_token(settings, "")?;
+
+        let auth = match credentials {
+            Credentials::Token => {
+                let client = Arc::new(Self::client_with_token(settings, &settings.token)?);
+                let lookup = token::lookup_self(client.as_ref())
+                    .await
+                    .map_err(VaultError::Login)?;
+
+                if lookup.ttl == 0 {
+                    warn!("Vault token never expires, prefer AppRole or Kubernetes auth");
+                } else if !lookup.renewable {
+                    warn!(
+                        "Vault token is not renewable and expires in {}s, Vault is unavailable \
+                         afterwards",
+                        lookup.ttl
+                    );
+                }
+
+                return Ok(Lease::new(client, lookup.ttl, lookup.renewable));
+            }
+            Credentials::AppRole {
+                mount,
+                role_id,
+                secret_id,
+            } => approle::login(&client, mount, role_id, secret_id).await,
+            Credentials::Kubernetes {
+                mount,
+                role,
+                token_path,
+            } => {
+                // Read on every login, the service account token is rotated by the kubelet.
+                let jwt = tokio::fs::read_to_string(token_path).await.map_err(|err| {
+                    VaultError::Config(format!("Can not read {token_path}: {err}"))
+                })?;
+
+                kubernetes::login(&client, mount, role, jwt.trim()).await
+            }
+        }
+        .map_err(VaultError::Login)?;
+
+        info!(
+            "Logged in to Vault, token valid for {}s",
+            auth.lease_duration
+        );
+
+        let client = Arc::new(Self::client_with_token(settings, &auth.client_token)?);
+
+        Ok(Lease::new(client, auth.lease_duration, auth.renewable))
+    }
+
+    /// `vaultrs::token::renew_self` requests a malformed path in vaultrs 0.7, the request is
+    /// sent with the client's own HTTP client instead.
+    async fn renew_self(client: &VaultClient) -> Result<AuthInfo, VaultError> {
+        let url = format!(
+            "{}/v1/auth/token/renew-self",
+            client.settings.address.as_str().trim_end_matches('/')
+        );
+
+        let mut request = client
+            .http
+            .http
+            .post(url)
+            .header("X-Vault-Token", &client.settings.token)
+            .body("{}");
+
+        if let Some(namespace) = &client.settings.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|err| VaultError::Renewal(err.to_string()))?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            return Err(VaultError::Renewal(format!(
+                "Vault responded with {status}"
+            )));
+        }
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| VaultError::Renewal(err.to_string()))?;
+
+        serde_json::from_slice::<RenewResponse>(&body)
+            .map(|response| response.auth)
+            .map_err(|err| VaultError::Renewal(err.to_string()))
+    }
+
+    /// Renews the token, or logs in again when it is not renewable or close to its max TTL.
+    async fn refresh(&self) -> Result<(), VaultError> {
+        let (client, ttl, renewable) = {
+            let lease = self.lease.read().expect("Vault lease lock poisoned");
+            (lease.client.clone(), lease.ttl, lease.renewable)
+        };
+
+        let can_login = !matches!(self.credentials, Credentials::Token);
+
+        let renewed = match renewable {
+            true => match Self::renew_self(&client).await {
+                Ok(auth) => Some(auth),
+                Err(err) if can_login => {
+                    warn!("{err}, logging in again");
+                    None
+                }
+                Err(err) => return Err(err),
+            },
+            false => None,
+        };
+
+        let lease = match renewed {
+            // The max TTL caps renewals, log in again before the token runs out.
+            Some(auth) if !can_login || Duration::from_secs(auth.lease_duration) * 2 > ttl => {
+                Lease::new(client, auth.lease_duration, auth.renewable)
+            }
+            _ if can_login => Self::login(&self.settings, &self.credentials).await?,
+            _ => {
+                return Err(VaultError::Renewal(
+                    "Vault token is not renewable".to_string(),
+                ))
+            }
+        };
+
+        *self.lease.write().expect("Vault lease lock poisoned") = lease;
+
+        Ok(())
+    }
+
+    fn schedule(&self) -> Schedule {
+        let lease = self.lease.read().expect("Vault lease lock poisoned");
+
+        let Some(expires) = lease.expires else {
+            return Schedule::Stop;
+        };
+
+        let remaining = expires.saturating_duration_since(Instant::now());
+        let can_login = !matches!(self.credentials, Credentials::Token);
+
+        // A static token is only renewed while it is renewable and has not expired.
+        if !can_login && (!lease.renewable || remaining.is_zero()) {
+            return Schedule::Expire(remaining);
+        }
+
+        let delay = match lease.failures {
+            0 => (lease.ttl * 2 / 3).max(MIN_RENEWAL_INTERVAL),
+            failures => RETRY_INTERVAL
+                .saturating_mul(2u32.saturating_pow(failures - 1))
+                .min(MAX_RETRY_INTERVAL),
+        };
+
+        Schedule::Refresh(match can_login {
+            true => delay,
+            false => delay.min(remaining),
+        })
+    }
+
+    fn spawn_renewal(self: &Arc<Self>) {
+        // Weak, the task stops with the last reference to the client.
+        let vault = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            loop {
+                let Some(schedule) = vault.upgrade().map(|vault| vault.schedule()) else {
+                    return;
+                };
+
+                match schedule {
+                    Schedule::Stop => return,
+                    Schedule::Expire(delay) => {
+                        tokio::time::sleep(delay).await;
+
+                        if vault.upgrade().is_some() {
+                            error!("{}, Vault is unavailable", VaultError::Expired);
+                        }
+
+                        return;
+                    }
+                    Schedule::Refresh(delay) => tokio::time::sleep(delay).await,
+                }
+
+                let Some(vault) = vault.upgrade() else {
+                    return;
+                };
+
+                if let Err(err) = vault.refresh().await {
+                    let mut lease = vault.lease.write().expect("Vault lease lock poisoned");
+                    lease.failures = lease.failures.saturating_add(1);
+
+                    let expired = lease
+                        .expires
+                        .is_some_and(|expires| expires <= Instant::now());
+
+                    match (expired, matches!(vault.credentials, Credentials::Token)) {
+                        // Reported once by `Schedule::Expire`.
+                        (true, true) => {}
+                        (true, false) => error!("Vault token expired, Vault is unavailable: {err}"),
+                        (false, _) => error!("{err}, retrying until the token expires"),
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Lease {
+    fn new(client: Arc<VaultClient>, ttl: u64, renewable: bool) -> Self {
+        Lease {
+            client,
+            ttl: Duration::from_secs(ttl),
+            renewable,
+            expires: (ttl > 0).then(|| Instant::now() + Duration::from_secs(ttl)),
+            failures: 0,
+        }
+    }
+}
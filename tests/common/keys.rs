@@ -0,0 +1,22 @@
+use sea_orm::entity::prelude::*;
+
+/// The columns of the `keys` table the tests inspect.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "keys")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub local_key: String,
+    pub local_index: String,
+    pub local_dek: Option<String>,
+    pub cloud_key: String,
+    pub cloud_version: Option<i64>,
+    pub commitments: Option<Json>,
+    pub total_shares: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,14 +1,44 @@
+#![allow(dead_code)]
+
 use actix_http::body::to_bytes;
 use actix_http::{Request, StatusCode};
 use actix_web::dev::{Service, ServiceResponse};
 use actix_web::{test, web::Bytes, Error};
 use serde::Serialize;
+use tokio::sync::Mutex;
+
+use kms::{AppData, Config, CreateUserResponse, KeysGenerateResponse};
+use migration::{Migrator, MigratorTrait};
+
+pub mod keys;
 
-use kms::{AppData, Config};
+static MIGRATIONS: Mutex<()> = Mutex::const_new(());
 
+/// The environment's configuration, or [`Config::in_memory`] when `DATABASE_URL` is not set.
+pub fn config() -> Config {
+    if std::env::var("DATABASE_URL").is_err() {
+        return Config::in_memory();
+    }
+
+    Config::default()
+}
+
+/// Migrated app data of [`config`].
 pub async fn setup() -> AppData {
-    let config = Config::default();
-    let app_data = AppData::new(&config).await;
+    setup_with_config(&config()).await
+}
+
+/// Migrated app data of a test specific configuration, e.g. `..config()` with another share
+/// storage.
+pub async fn setup_with_config(config: &Config) -> AppData {
+    let app_data = AppData::new(config).await;
+
+    let _guard = MIGRATIONS.lock().await;
+
+    Migrator::up(app_data.get_db_connection(), None)
+        .await
+        .expect("migration error");
+
     app_data
 }
 
@@ -43,6 +73,32 @@ where
     Ok((bytes, status))
 }
 
+pub async fn post_request_with_headers<T, D>(
+    app: &T,
+    url: &str,
+    data: D,
+    headers: &[(&str, &str)],
+) -> anyhow::Result<(Bytes, StatusCode)>
+where
+    T: Service<Request, Response = ServiceResponse, Error = Error>,
+    D: Serialize,
+{
+    let mut req = test::TestRequest::post()
+        .uri(&format!("/api{url}"))
+        .set_json(data);
+
+    for (name, value) in headers {
+        req = req.insert_header((name.to_string(), value.to_string()))
+    }
+
+    let resp = app.call(req.to_request()).await.unwrap();
+
+    let status = resp.status();
+    let bytes = to_bytes(resp.into_body()).await.unwrap();
+
+    Ok((bytes, status))
+}
+
 pub async fn post_request<T>(
     app: &T,
     url: &str,
@@ -96,3 +152,23 @@ where
 
     Ok((bytes, status))
 }
+
+pub async fn create_user_and_key<T>(app: &T) -> (String, KeysGenerateResponse)
+where
+    T: Service<Request, Response = ServiceResponse, Error = Error>,
+{
+    let (resp, status) = post_request(app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request(app, "/keys/generate", Some(&secret), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let key = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    (secret, key)
+}
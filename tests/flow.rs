@@ -7,9 +7,8 @@ use kms::{
     handlers, CreateUserResponse, KeysGenerateResponse, KeysRevokeRequest, SignMessageRequest,
     SignMessageResponse,
 };
-use migration::{Migrator, MigratorTrait};
 
-use crate::common::{get_request, post_request, post_request_with_data};
+use crate::common::{post_request, post_request_with_data};
 
 mod common;
 
@@ -22,10 +21,6 @@ async fn test_flow() {
 
     let app_data = common::setup().await;
 
-    Migrator::up(app_data.get_db_connection(), None)
-        .await
-        .expect("migration error");
-
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(app_data.clone()))
@@ -39,7 +34,7 @@ async fn test_flow() {
 
     let CreateUserResponse { secret } =
         serde_json::from_slice(&resp).expect("Failed to parse response");
-    assert!(secret.len() > 0);
+    assert!(!secret.is_empty());
 
     let (resp, status) = post_request(&app, "/keys/generate", Some(&secret), None)
         .await
@@ -48,8 +43,7 @@ async fn test_flow() {
     assert_eq!(status, StatusCode::OK);
 
     let KeysGenerateResponse {
-        key: key_share_a,
-        id: _,
+        key: key_share_a, ..
     } = serde_json::from_slice(&resp).expect("Failed to parse response");
 
     warn!("Key #a: {key_share_a}");
@@ -61,6 +55,7 @@ async fn test_flow() {
         "/sign_message",
         Some(SignMessageRequest {
             message: message.to_string(),
+            ..Default::default()
         }),
         None,
         Some(&key_share_a),
@@ -85,6 +80,7 @@ async fn test_flow() {
     let KeysGenerateResponse {
         key: key_share_b,
         id: id_share_b,
+        ..
     } = serde_json::from_slice(&resp).expect("Failed to parse response");
 
     warn!("Key #b: {key_share_b}");
@@ -96,6 +92,7 @@ async fn test_flow() {
         "/sign_message",
         Some(SignMessageRequest {
             message: message.to_string(),
+            ..Default::default()
         }),
         None,
         Some(&key_share_b),
@@ -130,6 +127,7 @@ async fn test_flow() {
         "/sign_message",
         Some(SignMessageRequest {
             message: message.to_string(),
+            ..Default::default()
         }),
         None,
         Some(&key_share_b),
@@ -0,0 +1,1170 @@
+use actix_http::StatusCode;
+use actix_web::{test, web, App};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use kms::{
+    handlers, AppData, Config, CreateUserResponse, KeysCheckResponse, KeysGenerateRequest,
+    KeysGenerateResponse, KeysGrantRequest, KeysRefreshRequest, KeysReshareRequest,
+    KeysRollbackRequest, KeysRollbackResponse, ShareFormat, ShareStorageKind, SignMessageRequest,
+    SignMessageResponse, VerifyPayload, VerifyRequest, VerifyResponse,
+};
+
+use crate::common::{
+    create_user_and_key, keys, post_request, post_request_with_data, post_request_with_headers,
+};
+
+mod common;
+
+#[tokio::test]
+async fn test_threshold() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    for (shares, threshold) in [(3, 2), (3, 4), (100, 4)] {
+        let (_resp, status) = post_request_with_data(
+            &app,
+            "/keys/generate",
+            Some(KeysGenerateRequest {
+                shares: Some(shares),
+                threshold: Some(threshold),
+                ..Default::default()
+            }),
+            Some(&secret),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            shares: Some(5),
+            threshold: Some(4),
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key_id, shares, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(shares.len(), 3);
+
+    let message = "Hello, world!";
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: message.to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&shares[0].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = post_request(
+        &app,
+        "/keys/grant",
+        Some(&secret),
+        Some(&format!("{},{}", shares[0].key, shares[1].key)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: granted, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let pairs = [
+        format!("{},{}", shares[0].key, shares[1].key),
+        format!("{},{}", shares[2].key, shares[0].key),
+        format!("{},{}", granted, shares[1].key),
+    ];
+
+    let mut signatures = vec![];
+
+    for pair in pairs.iter() {
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/sign_message",
+            Some(SignMessageRequest {
+                message: message.to_string(),
+                ..Default::default()
+            }),
+            None,
+            Some(pair),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let SignMessageResponse { signature } =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        signatures.push(signature);
+    }
+
+    assert!(signatures
+        .iter()
+        .all(|signature| signature == &signatures[0]));
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/verify",
+        Some(VerifyRequest {
+            payload: VerifyPayload::Message(message.into()),
+            signature: signatures[0].clone(),
+            key_id: Some(key_id),
+        }),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let VerifyResponse { valid, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(valid, Some(true));
+}
+
+#[tokio::test]
+async fn test_recovery() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse {
+        secret: other_secret,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            recovery_shares: 2,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        key,
+        recovery_shares,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(recovery_shares.len(), 2);
+
+    let message = "Hello, world!";
+
+    let sign = |secret_key: String| {
+        let app = &app;
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: message.to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&secret_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let (resp, status) = sign(key.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (_resp, status) = sign(recovery_shares[0].key.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_resp, status) = post_request(
+        &app,
+        "/keys/grant",
+        Some(&secret),
+        Some(&recovery_shares[0].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_resp, status) = post_request(
+        &app,
+        "/keys/recover",
+        Some(&other_secret),
+        Some(&recovery_shares[0].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (resp, status) = post_request(&app, "/keys/grant", Some(&secret), Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: guest, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    // Recovery requires a recovery share, a guest or admin share is not promoted.
+    for share in [guest, key.clone()] {
+        let (_resp, status) = post_request(&app, "/keys/recover", Some(&secret), Some(&share))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let (resp, status) = post_request(
+        &app,
+        "/keys/recover",
+        Some(&secret),
+        Some(&recovery_shares[1].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: recovered, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    // A recovery share recovers the key only once.
+    let (_resp, status) = post_request(
+        &app,
+        "/keys/recover",
+        Some(&secret),
+        Some(&recovery_shares[1].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (resp, status) = sign(recovered).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse {
+        signature: recovered_signature,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(signature, recovered_signature);
+}
+
+#[tokio::test]
+async fn test_refresh() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            recovery_shares: 1,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        key,
+        key_id,
+        recovery_shares,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request(&app, "/keys/grant", Some(&secret), Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        key: guest,
+        id: guest_id,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let message = "Hello, world!";
+
+    let sign = |secret_key: String| {
+        let app = &app;
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: message.to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&secret_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let (resp, status) = sign(key.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/refresh",
+        Some(KeysRefreshRequest {
+            recovery_shares: 1,
+            ..Default::default()
+        }),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        key: refreshed,
+        shares: refreshed_shares,
+        recovery_shares: refreshed_recovery_shares,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_ne!(key, refreshed);
+
+    // The guest and recovery shares that were not presented are re-issued, not revoked.
+    assert_eq!(refreshed_shares.len(), 2);
+    assert_eq!(refreshed_recovery_shares.len(), 2);
+    assert_eq!(
+        refreshed_recovery_shares[0].previous_id,
+        Some(recovery_shares[0].id)
+    );
+    assert_eq!(refreshed_recovery_shares[1].previous_id, None);
+
+    let refreshed_guest = refreshed_shares
+        .iter()
+        .find(|share| share.previous_id == Some(guest_id))
+        .expect("Guest share not re-issued");
+
+    for refreshed in [refreshed, refreshed_guest.key.clone()] {
+        let (resp, status) = sign(refreshed).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let SignMessageResponse {
+            signature: refreshed_signature,
+        } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        assert_eq!(signature, refreshed_signature);
+    }
+
+    // Cloud, local, admin and guest share.
+    let row = keys::Entity::find_by_id(key_id)
+        .one(app_data.get_db_connection())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.total_shares, 4);
+
+    for old in [key, guest] {
+        let (_resp, status) = sign(old).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (_resp, status) = post_request(
+        &app,
+        "/keys/recover",
+        Some(&secret),
+        Some(&recovery_shares[0].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (resp, status) = post_request(
+        &app,
+        "/keys/recover",
+        Some(&secret),
+        Some(&refreshed_recovery_shares[0].key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: recovered, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = sign(recovered).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse {
+        signature: recovered_signature,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(signature, recovered_signature);
+}
+
+#[tokio::test]
+async fn test_check() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_secret, KeysGenerateResponse { key, key_id, .. }) =
+        common::create_user_and_key(&app).await;
+
+    let (resp, status) = post_request(&app, "/keys/check", None, Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let check: KeysCheckResponse = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(check.key_id, key_id);
+    assert!(check.valid && check.share && check.local && check.cloud && check.address);
+
+    keys::Entity::update_many()
+        .col_expr(keys::Column::LocalKey, Expr::value("01"))
+        .filter(keys::Column::Id.eq(key_id))
+        .exec(app_data.get_db_connection())
+        .await
+        .unwrap();
+
+    let (resp, status) = post_request(&app, "/keys/check", None, Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let check: KeysCheckResponse = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert!(!check.valid && !check.local);
+    assert!(check.share && check.cloud && check.address);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_reshare() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, KeysGenerateResponse { key, .. }) = common::create_user_and_key(&app).await;
+
+    let sign = |secret_key: String| {
+        let app = &app;
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: "Hello, world!".to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&secret_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let (resp, status) = sign(key.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/reshare",
+        Some(KeysReshareRequest {
+            shares: 5,
+            threshold: 2,
+            ..Default::default()
+        }),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/reshare",
+        Some(KeysReshareRequest {
+            shares: 5,
+            threshold: 4,
+            recovery_shares: 1,
+            ..Default::default()
+        }),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        shares,
+        recovery_shares,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(shares.len(), 3);
+    assert_eq!(recovery_shares.len(), 1);
+
+    let (_resp, status) = sign(key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_resp, status) = sign(shares[0].key.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = sign(format!("{},{}", shares[0].key, shares[2].key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse {
+        signature: reshared_signature,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(signature, reshared_signature);
+}
+
+#[tokio::test]
+async fn test_share_token() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_secret, KeysGenerateResponse { key, .. }) = common::create_user_and_key(&app).await;
+    assert!(key.starts_with("kms_"));
+
+    let sign_message = |secret_key: String| {
+        let app = &app;
+
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: "Hello, world!".to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&secret_key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let (_resp, status) = sign_message(key.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut typo = key.clone().into_bytes();
+    let index = typo.len() / 2;
+    typo[index] = if typo[index] == b'A' { b'B' } else { b'A' };
+
+    let (resp, status) = sign_message(String::from_utf8(typo).unwrap()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let error: serde_json::Value = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(error["error"].as_str().unwrap().contains("checksum"));
+
+    // Tokens issued before versioning only carried the share value.
+    let payload = URL_SAFE_NO_PAD.decode(&key[4..]).unwrap();
+    let y = &payload[49..81];
+    let y = &y[y.iter().take_while(|byte| **byte == 0).count()..];
+
+    let (_resp, status) = sign_message(STANDARD.encode(y)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_mnemonic_shares() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            format: ShareFormat::Mnemonic,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let words = key.split(' ').collect::<Vec<_>>();
+    assert_eq!(words.len(), 68);
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    // Words come back from paper in any case and may be abbreviated to four letters.
+    let transcribed = words
+        .iter()
+        .map(|word| word[..word.len().min(4)].to_uppercase())
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/grant",
+        Some(KeysGrantRequest {
+            secret_key: Some(transcribed),
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: granted, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(granted.starts_with("kms_"));
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&granted),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse {
+        signature: granted_signature,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(signature, granted_signature);
+
+    let mut misspelled = words.clone();
+    misspelled.swap(10, 11);
+
+    let (resp, status) = post_request(&app, "/keys/check", None, Some(&misspelled.join(" ")))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let error: serde_json::Value = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(error["error"].as_str().unwrap().contains("checksum"));
+}
+
+#[tokio::test]
+async fn test_passphrase_shares() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (resp, status) = post_request(&app, "/users", None, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let CreateUserResponse { secret } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            passphrase: Some(String::new()),
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            passphrase: Some("correct horse".to_string()),
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let message = SignMessageRequest {
+        message: "Hello, world!".to_string(),
+        ..Default::default()
+    };
+
+    // The share string alone is not enough to sign.
+    let (resp, status) = post_request_with_data(&app, "/sign_message", &message, None, Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let error: serde_json::Value = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(error["error"].as_str().unwrap().contains("passphrase"));
+
+    let (_resp, status) = post_request_with_headers(
+        &app,
+        "/sign_message",
+        &message,
+        &[("x-secret-key", &key), ("x-passphrase", "battery staple")],
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_resp, status) = post_request_with_headers(
+        &app,
+        "/sign_message",
+        &message,
+        &[("x-secret-key", &key), ("x-passphrase", "correct horse")],
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/grant",
+        KeysGrantRequest::default(),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (resp, status) = post_request_with_headers(
+        &app,
+        "/keys/grant",
+        KeysGrantRequest::default(),
+        &[
+            ("x-master-key", &secret),
+            ("x-secret-key", &key),
+            ("x-passphrase", "correct horse"),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: granted, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (_resp, status) =
+        post_request_with_data(&app, "/sign_message", &message, None, Some(&granted))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn cloud_key(app_data: &AppData, key_id: Uuid) -> (String, Uuid, Option<i64>) {
+    let key = keys::Entity::find_by_id(key_id)
+        .one(app_data.get_db_connection())
+        .await
+        .unwrap()
+        .expect("Key not found");
+
+    (key.cloud_key, key.user_id, key.cloud_version)
+}
+
+#[tokio::test]
+async fn test_share_path_template() {
+    let app_data = common::setup_with_config(&Config {
+        share_path_template: Some("kms/{env}/{user_id}/{key_id}".to_string()),
+        environment: Some("test".to_string()),
+        ..common::config()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, KeysGenerateResponse { key, key_id, .. }) = create_user_and_key(&app).await;
+
+    let (path, user_id, version) = cloud_key(&app_data, key_id).await;
+    assert_eq!(version, Some(1));
+    let prefix = format!("kms/test/{user_id}/{key_id}/");
+    assert!(path.starts_with(&prefix), "Unexpected share path {path}");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/refresh",
+        Some(KeysRefreshRequest::default()),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    // Vault keeps versions, the refreshed share is a new version at the same path.
+    let (refreshed_path, _, version) = cloud_key(&app_data, key_id).await;
+    assert_eq!(path, refreshed_path);
+    assert_eq!(version, Some(2));
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_rollback() {
+    let app_data = common::setup_with_config(&Config {
+        key_versions_retained: Some(1),
+        ..common::config()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, KeysGenerateResponse { key, key_id, .. }) = create_user_and_key(&app).await;
+
+    let refresh = |key: String, keep_previous: bool| {
+        let app = &app;
+        let secret = &secret;
+        async move {
+            let (resp, status) = post_request_with_data(
+                app,
+                "/keys/refresh",
+                Some(KeysRefreshRequest {
+                    keep_previous,
+                    ..Default::default()
+                }),
+                Some(secret),
+                Some(&key),
+            )
+            .await
+            .unwrap();
+            assert_eq!(status, StatusCode::OK);
+
+            let response: KeysGenerateResponse =
+                serde_json::from_slice(&resp).expect("Failed to parse response");
+            (response.key, response.id)
+        }
+    };
+
+    let sign = |key: String| {
+        let app = &app;
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: "Hello, world!".to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&key),
+            )
+            .await
+            .unwrap()
+            .1
+        }
+    };
+
+    let rollback = |version: i64| {
+        let app = &app;
+        let secret = &secret;
+        async move {
+            post_request_with_data(
+                app,
+                "/keys/rollback",
+                Some(KeysRollbackRequest { key_id, version }),
+                Some(secret),
+                None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    // Without keep_previous the replaced sharing is gone.
+    let (refreshed, refreshed_id) = refresh(key, false).await;
+    assert_eq!(rollback(1).await.1, StatusCode::NOT_FOUND);
+
+    let (third, _) = refresh(refreshed.clone(), true).await;
+    assert_eq!(sign(refreshed.clone()).await, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = rollback(2).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysRollbackResponse { share_ids, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(share_ids.contains(&refreshed_id));
+    assert_eq!(cloud_key(&app_data, key_id).await.2, Some(2));
+
+    assert_eq!(sign(refreshed.clone()).await, StatusCode::OK);
+    assert_eq!(sign(third.clone()).await, StatusCode::BAD_REQUEST);
+
+    assert_eq!(rollback(2).await.1, StatusCode::BAD_REQUEST);
+    assert_eq!(rollback(7).await.1, StatusCode::NOT_FOUND);
+
+    // The rolled back version is kept as well, the rollback can be undone.
+    assert_eq!(rollback(3).await.1, StatusCode::OK);
+    assert_eq!(sign(third.clone()).await, StatusCode::OK);
+    assert_eq!(sign(refreshed).await, StatusCode::BAD_REQUEST);
+
+    // Only the most recently replaced sharing is retained.
+    refresh(third, true).await;
+    assert_eq!(rollback(2).await.1, StatusCode::NOT_FOUND);
+    assert_eq!(rollback(3).await.1, StatusCode::OK);
+
+    // Keys only keep versions when retention is configured.
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, KeysGenerateResponse { key, key_id, .. }) = create_user_and_key(&app).await;
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/refresh",
+        Some(KeysRefreshRequest {
+            keep_previous: true,
+            ..Default::default()
+        }),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/rollback",
+        Some(KeysRollbackRequest { key_id, version: 1 }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+fn count_share_files(dir: &std::path::Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .map(|path| match path.is_dir() {
+            true => count_share_files(&path),
+            false => 1,
+        })
+        .sum()
+}
+
+#[tokio::test]
+async fn test_generate_cleanup() {
+    let dir = std::env::temp_dir().join(format!("kms-shares-{}", Uuid::new_v4()));
+
+    // SQLite in memory whatever the environment, its shares table is dropped below.
+    let app_data = common::setup_with_config(&Config {
+        share_storage: ShareStorageKind::File,
+        share_storage_dir: Some(dir.to_str().unwrap().to_string()),
+        share_storage_key: Some("11".repeat(32)),
+        ..Config::in_memory()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, _) = create_user_and_key(&app).await;
+    assert_eq!(count_share_files(&dir), 1);
+
+    app_data
+        .get_db_connection()
+        .execute_unprepared("DROP TABLE shares")
+        .await
+        .unwrap();
+
+    let (_resp, status) = post_request(&app, "/keys/generate", Some(&secret), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    // Neither the key nor its cloud share are left behind.
+    assert_eq!(count_share_files(&dir), 1);
+    assert_eq!(
+        keys::Entity::find()
+            .all(app_data.get_db_connection())
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
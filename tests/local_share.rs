@@ -0,0 +1,246 @@
+use actix_http::StatusCode;
+use actix_web::{test, web, App};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use kms::{
+    encrypt_local_shares, handlers, AppData, Config, KeysGenerateResponse, KeysRefreshRequest,
+    KeysRollbackRequest, SignMessageRequest, SignMessageResponse,
+};
+
+use crate::common::{create_user_and_key, keys, post_request, post_request_with_data};
+
+mod common;
+
+async fn local_share_columns(app_data: &AppData, key_id: Uuid) -> (String, Option<String>) {
+    let key = keys::Entity::find_by_id(key_id)
+        .one(app_data.get_db_connection())
+        .await
+        .unwrap()
+        .expect("Key not found");
+
+    (key.local_key, key.local_dek)
+}
+
+// A single test, the backfill encrypts every plaintext row of the database.
+#[tokio::test]
+#[ignore = "requires Postgres and a Vault transit engine"]
+async fn test_local_share_encryption() {
+    let plain_data = common::setup_with_config(&Config {
+        key_versions_retained: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let local_data = common::setup_with_config(&Config {
+        local_kek: Some("11".repeat(32)),
+        key_versions_retained: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let transit_data = common::setup_with_config(&Config {
+        vault_transit_key: Some("kms".to_string()),
+        vault_transit_mount: Some("transit".to_string()),
+        ..Default::default()
+    })
+    .await;
+
+    let plain = test::init_service(
+        App::new()
+            .app_data(web::Data::new(plain_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+    let local = test::init_service(
+        App::new()
+            .app_data(web::Data::new(local_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+    let transit = test::init_service(
+        App::new()
+            .app_data(web::Data::new(transit_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let message = SignMessageRequest {
+        message: "Hello, world!".to_string(),
+        ..Default::default()
+    };
+
+    let (secret, KeysGenerateResponse { key, key_id, .. }) = create_user_and_key(&plain).await;
+
+    let (local_key, local_dek) = local_share_columns(&plain_data, key_id).await;
+    assert!(local_dek.is_none());
+    assert!(hex::decode(&local_key).is_ok());
+
+    let (resp, status) =
+        post_request_with_data(&plain, "/sign_message", &message, None, Some(&key))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    // A kept version of another key, its local share is encrypted by the backfill too.
+    let (versioned_secret, versioned) = create_user_and_key(&plain).await;
+
+    let (_resp, status) = post_request_with_data(
+        &plain,
+        "/keys/refresh",
+        KeysRefreshRequest {
+            keep_previous: true,
+            ..Default::default()
+        },
+        Some(&versioned_secret),
+        Some(&versioned.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    assert!(encrypt_local_shares(&local_data).await.unwrap() >= 3);
+    assert_eq!(encrypt_local_shares(&local_data).await.unwrap(), 0);
+
+    let (encrypted_key, local_dek) = local_share_columns(&local_data, key_id).await;
+    assert!(local_dek.is_some_and(|dek| dek.starts_with("local:")));
+    assert_ne!(encrypted_key, local_key);
+
+    let (resp, status) =
+        post_request_with_data(&local, "/sign_message", &message, None, Some(&key))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse {
+        signature: encrypted_signature,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(signature, encrypted_signature);
+
+    // Without the key-encryption key the local share is useless.
+    let (_resp, status) =
+        post_request_with_data(&plain, "/sign_message", &message, None, Some(&key))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (resp, status) = post_request(&local, "/keys/grant", Some(&secret), Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: granted, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (_resp, status) =
+        post_request_with_data(&local, "/sign_message", &message, None, Some(&granted))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let (_resp, status) = post_request_with_data(
+        &local,
+        "/keys/rollback",
+        KeysRollbackRequest {
+            key_id: versioned.key_id,
+            version: 1,
+        },
+        Some(&versioned_secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let (_resp, status) = post_request_with_data(
+        &local,
+        "/sign_message",
+        &message,
+        None,
+        Some(&versioned.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    // The encrypted local share is bound to its key, even along with its data key.
+    let sealed = keys::Entity::find_by_id(key_id)
+        .one(local_data.get_db_connection())
+        .await
+        .unwrap()
+        .expect("Key not found");
+
+    keys::Entity::update_many()
+        .col_expr(keys::Column::LocalKey, Expr::value(sealed.local_key))
+        .col_expr(keys::Column::LocalIndex, Expr::value(sealed.local_index))
+        .col_expr(keys::Column::LocalDek, Expr::value(sealed.local_dek))
+        .filter(keys::Column::Id.eq(versioned.key_id))
+        .exec(local_data.get_db_connection())
+        .await
+        .unwrap();
+
+    let (resp, status) = post_request_with_data(
+        &local,
+        "/sign_message",
+        &message,
+        None,
+        Some(&versioned.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(String::from_utf8_lossy(&resp).contains("could not be decrypted"));
+
+    let (_secret, KeysGenerateResponse { key, key_id, .. }) = create_user_and_key(&transit).await;
+
+    let (_local_key, local_dek) = local_share_columns(&transit_data, key_id).await;
+    assert!(local_dek.is_some_and(|dek| dek.starts_with("vault:")));
+
+    let (_resp, status) =
+        post_request_with_data(&transit, "/sign_message", &message, None, Some(&key))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let (_resp, status) =
+        post_request_with_data(&local, "/sign_message", &message, None, Some(&key))
+            .await
+            .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_missing_kek() {
+    let path = std::env::temp_dir().join(format!("kms-{}.db", Uuid::new_v4()));
+    let database_url = format!("sqlite://{}?mode=rwc", path.display());
+
+    let plain_data = common::setup_with_config(&Config {
+        database_url: database_url.clone(),
+        ..Config::in_memory()
+    })
+    .await;
+    let local_data = common::setup_with_config(&Config {
+        database_url,
+        local_kek: Some("11".repeat(32)),
+        ..Config::in_memory()
+    })
+    .await;
+
+    let plain = test::init_service(
+        App::new()
+            .app_data(web::Data::new(plain_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    create_user_and_key(&plain).await;
+
+    // Plaintext local shares are accepted until the first one is encrypted.
+    assert_eq!(encrypt_local_shares(&plain_data).await.unwrap(), 0);
+    assert_eq!(encrypt_local_shares(&local_data).await.unwrap(), 1);
+    assert!(encrypt_local_shares(&plain_data).await.is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
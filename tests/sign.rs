@@ -0,0 +1,999 @@
+use std::str::FromStr;
+
+use actix_http::StatusCode;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, web, App, Error};
+use alloy::consensus::TxEnvelope;
+use alloy::eips::eip2718::Decodable2718;
+use alloy::primitives::{eip191_hash_message, keccak256, Address, Signature, B256};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use k256::schnorr::signature::hazmat::PrehashVerifier;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+
+use kms::{
+    handlers, Config, EncodedMessage, KeyType, KeysCheckResponse, KeysGenerateRequest,
+    KeysGenerateResponse, KeysRefreshRequest, KeysRevokeRequest, MessageEncoding, ShareStorageKind,
+    SignBatchItem, SignBatchRequest, SignBatchResponse, SignHashRequest, SignHashResponse,
+    SignMessageRequest, SignMessageResponse, SignSchnorrRequest, SignSchnorrResponse,
+    SignTransactionRequest, SignTransactionResponse, SignTypedDataRequest, SigningMode,
+    VerifyPayload, VerifyRequest, VerifyResponse,
+};
+
+use crate::common::{create_user_and_key, get_request, keys, post_request, post_request_with_data};
+
+mod common;
+
+async fn get_address<T>(app: &T, key: &str) -> Address
+where
+    T: Service<actix_http::Request, Response = ServiceResponse, Error = Error>,
+{
+    let message = "Hello, world!";
+
+    let (resp, status) = post_request_with_data(
+        app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: message.to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    Signature::from_str(&signature)
+        .expect("Invalid signature")
+        .recover_address_from_msg(message)
+        .expect("Recover error")
+}
+
+#[tokio::test]
+async fn test_sign_typed_data() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    let address = get_address(&app, &key.key).await;
+
+    let request: SignTypedDataRequest = serde_json::from_value(json!({
+        "types": {
+            "EIP712Domain": [
+                {"name": "name", "type": "string"},
+                {"name": "version", "type": "string"},
+                {"name": "chainId", "type": "uint256"},
+                {"name": "verifyingContract", "type": "address"}
+            ],
+            "Permit": [
+                {"name": "owner", "type": "address"},
+                {"name": "spender", "type": "address"},
+                {"name": "value", "type": "uint256"},
+                {"name": "nonce", "type": "uint256"},
+                {"name": "deadline", "type": "uint256"}
+            ]
+        },
+        "primaryType": "Permit",
+        "domain": {
+            "name": "USD Coin",
+            "version": "2",
+            "chainId": 1,
+            "verifyingContract": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+        },
+        "message": {
+            "owner": address.to_string(),
+            "spender": "0x1111111254eeb25477b68fb85ed929f73a960582",
+            "value": "1000000",
+            "nonce": 0,
+            "deadline": 1893456000
+        }
+    }))
+    .expect("Invalid typed data");
+
+    let hash = request
+        .typed_data
+        .eip712_signing_hash()
+        .expect("Hash error");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_typed_data",
+        Some(request),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let recovered = Signature::from_str(&signature)
+        .expect("Invalid signature")
+        .recover_address_from_prehash(&hash)
+        .expect("Recover error");
+
+    assert_eq!(address, recovered);
+}
+
+#[tokio::test]
+async fn test_sign_transaction() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    let address = get_address(&app, &key.key).await;
+
+    let requests = vec![
+        SignTransactionRequest {
+            chain_id: 1,
+            nonce: 7,
+            gas_limit: 21000,
+            gas_price: Some(20_000_000_000),
+            to: Some(Address::repeat_byte(0x11)),
+            value: "1000000000000000000".parse().unwrap(),
+            ..Default::default()
+        },
+        SignTransactionRequest {
+            chain_id: 1,
+            nonce: 8,
+            gas_limit: 60000,
+            gas_price: Some(20_000_000_000),
+            to: Some(Address::repeat_byte(0x22)),
+            data: vec![0xa9, 0x05, 0x9c, 0xbb, 0x00].into(),
+            access_list: Some(Default::default()),
+            ..Default::default()
+        },
+        SignTransactionRequest {
+            chain_id: 10,
+            nonce: 9,
+            gas_limit: 60000,
+            max_fee_per_gas: Some(30_000_000_000),
+            max_priority_fee_per_gas: Some(1_000_000_000),
+            to: Some(Address::repeat_byte(0x33)),
+            ..Default::default()
+        },
+    ];
+
+    for (request, tx_type) in requests.into_iter().zip([0u8, 1, 2]) {
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/sign_transaction",
+            Some(request),
+            None,
+            Some(&key.key),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let SignTransactionResponse { transaction, hash } =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        let raw = hex::decode(transaction).expect("Invalid transaction hex");
+        assert_eq!(hex::encode(keccak256(&raw)), hash);
+
+        let envelope = TxEnvelope::decode_2718(&mut raw.as_slice()).expect("Decode error");
+        assert_eq!(u8::from(envelope.tx_type()), tx_type);
+        assert_eq!(envelope.recover_signer().expect("Recover error"), address);
+    }
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_transaction",
+        Some(SignTransactionRequest {
+            chain_id: 1,
+            gas_limit: 21000,
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_sign_hash() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, key) = create_user_and_key(&app).await;
+
+    let hash = keccak256("raw digest");
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_hash",
+        Some(SignHashRequest { hash }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (resp, status) = get_request(&app, &format!("/logs/{}", key.key_id), Some(&secret), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let logs: Vec<serde_json::Value> =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(logs.iter().any(|log| log["action"] == "sign_hash_refused"));
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            allow_raw_hash: true,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    let address = get_address(&app, &key).await;
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_hash",
+        Some(SignHashRequest { hash }),
+        None,
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignHashResponse { signature, r, s, v } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert!(v == 27 || v == 28);
+    assert_eq!(signature, format!("{r}{s}{v:02x}"));
+
+    let recovered = Signature::from_str(&signature)
+        .expect("Invalid signature")
+        .recover_address_from_prehash(&B256::from(hash))
+        .expect("Recover error");
+
+    assert_eq!(address, recovered);
+}
+
+#[tokio::test]
+async fn test_sign_batch() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+
+    let messages = ["first", "second", "third"];
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_batch",
+        Some(SignBatchRequest {
+            items: messages
+                .iter()
+                .map(|message| SignBatchItem::Message((*message).into()))
+                .collect(),
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignBatchResponse { signatures } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(signatures.len(), messages.len());
+
+    for (message, batch_signature) in messages.iter().zip(signatures.iter()) {
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/sign_message",
+            Some(SignMessageRequest {
+                message: message.to_string(),
+                ..Default::default()
+            }),
+            None,
+            Some(&key.key),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let SignMessageResponse { signature } =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        assert_eq!(&signature, batch_signature);
+    }
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_batch",
+        Some(SignBatchRequest { items: vec![] }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_verify() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    let (_, other_key) = create_user_and_key(&app).await;
+
+    let message = "Hello, world!";
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: message.to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let address = get_address(&app, &key.key).await;
+
+    let cases = [
+        (key.key_id, Some(true)),
+        (other_key.key_id, Some(false)),
+        (uuid::Uuid::new_v4(), Some(false)),
+    ];
+
+    for (key_id, valid) in cases {
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/verify",
+            Some(VerifyRequest {
+                payload: VerifyPayload::Message(message.into()),
+                signature: signature.clone(),
+                key_id: Some(key_id),
+            }),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let response: VerifyResponse =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        assert_eq!(response.address, address);
+        assert_eq!(response.valid, valid);
+    }
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/verify",
+        Some(VerifyRequest {
+            payload: VerifyPayload::Hash(eip191_hash_message(message)),
+            signature,
+            key_id: None,
+        }),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let response: VerifyResponse = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    assert_eq!(response.address, address);
+    assert_eq!(response.valid, None);
+}
+
+#[tokio::test]
+async fn test_sign_message_encoding() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    let address = get_address(&app, &key.key).await;
+
+    let challenge = keccak256("challenge");
+
+    let requests = [
+        (
+            format!("0x{}", hex::encode(challenge)),
+            MessageEncoding::Hex,
+        ),
+        (hex::encode(challenge), MessageEncoding::Hex),
+        (STANDARD.encode(challenge), MessageEncoding::Base64),
+    ];
+
+    for (message, encoding) in requests {
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/sign_message",
+            Some(SignMessageRequest {
+                message: message.clone(),
+                encoding,
+            }),
+            None,
+            Some(&key.key),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let SignMessageResponse { signature } =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+
+        let recovered = Signature::from_str(&signature)
+            .expect("Invalid signature")
+            .recover_address_from_msg(challenge)
+            .expect("Recover error");
+
+        assert_eq!(address, recovered);
+
+        let (resp, status) = post_request_with_data(
+            &app,
+            "/verify",
+            Some(VerifyRequest {
+                payload: VerifyPayload::Message(EncodedMessage { message, encoding }),
+                signature: signature.clone(),
+                key_id: Some(key.key_id),
+            }),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let response: VerifyResponse =
+            serde_json::from_slice(&resp).expect("Failed to parse response");
+        assert_eq!(response.valid, Some(true));
+    }
+
+    // Batch items take the same encodings, plain strings stay UTF-8.
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_batch",
+        Some(json!({
+            "items": [
+                {"message": {"message": hex::encode(challenge), "encoding": "hex"}},
+                {"message": "challenge"},
+            ]
+        })),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignBatchResponse { signatures } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    for (signature, message) in signatures.iter().zip([challenge.as_slice(), b"challenge"]) {
+        let recovered = Signature::from_str(signature)
+            .expect("Invalid signature")
+            .recover_address_from_msg(message)
+            .expect("Recover error");
+
+        assert_eq!(address, recovered);
+    }
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "not hex".to_string(),
+            encoding: MessageEncoding::Hex,
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_additive_signing() {
+    let config = Config {
+        signing_mode: SigningMode::Additive,
+        ..common::config()
+    };
+    let app_data = common::setup_with_config(&config).await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+
+    let message = "Hello, world!";
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: message.to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/verify",
+        Some(VerifyRequest {
+            payload: VerifyPayload::Message(message.into()),
+            signature,
+            key_id: Some(key.key_id),
+        }),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let VerifyResponse { address, valid } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(valid, Some(true));
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_transaction",
+        Some(SignTransactionRequest {
+            chain_id: 1,
+            nonce: 7,
+            gas_limit: 21000,
+            gas_price: Some(20_000_000_000),
+            to: Some(Address::repeat_byte(0x11)),
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignTransactionResponse { transaction, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let raw = hex::decode(transaction).expect("Invalid transaction hex");
+    let envelope = TxEnvelope::decode_2718(&mut raw.as_slice()).expect("Decode error");
+    assert_eq!(envelope.recover_signer().expect("Recover error"), address);
+}
+
+#[tokio::test]
+async fn test_sign_schnorr() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, ecdsa_key) = create_user_and_key(&app).await;
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            key_type: KeyType::Schnorr,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let key: KeysGenerateResponse =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request(&app, "/keys/check", None, Some(&key.key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let check: KeysCheckResponse = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(check.valid);
+
+    let message = keccak256("Hello, world!");
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_schnorr",
+        Some(SignSchnorrRequest { message }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let SignSchnorrResponse {
+        signature,
+        public_key,
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let public_key = k256::schnorr::VerifyingKey::from_bytes(&hex::decode(public_key).unwrap())
+        .expect("Invalid public key");
+    let signature = k256::schnorr::Signature::try_from(hex::decode(signature).unwrap().as_slice())
+        .expect("Invalid signature");
+
+    public_key
+        .verify_prehash(message.as_slice(), &signature)
+        .expect("Signature verification failed");
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_schnorr",
+        Some(SignSchnorrRequest { message }),
+        None,
+        Some(&ecdsa_key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Without commitments a corrupted share is only caught when the signature fails to verify.
+    keys::Entity::update_many()
+        .col_expr(keys::Column::LocalKey, Expr::value("01"))
+        .col_expr(
+            keys::Column::Commitments,
+            Expr::value(Option::<serde_json::Value>::None),
+        )
+        .filter(keys::Column::Id.eq(key.key_id))
+        .exec(app_data.get_db_connection())
+        .await
+        .unwrap();
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/sign_schnorr",
+        Some(SignSchnorrRequest { message }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_legacy_local_index() {
+    let app_data = common::setup().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, key) = create_user_and_key(&app).await;
+
+    // Keys created before `local_index` held the x coordinate stored y in both columns.
+    keys::Entity::update_many()
+        .col_expr(
+            keys::Column::LocalIndex,
+            Expr::col(keys::Column::LocalKey).into(),
+        )
+        .filter(keys::Column::Id.eq(key.key_id))
+        .exec(app_data.get_db_connection())
+        .await
+        .unwrap();
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/sign_message",
+        Some(SignMessageRequest {
+            message: "Hello, world!".to_string(),
+            ..Default::default()
+        }),
+        None,
+        Some(&key.key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(String::from_utf8_lossy(&resp).contains("recreate the key"));
+
+    let (resp, status) = post_request(&app, "/keys/grant", Some(&secret), Some(&key.key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(String::from_utf8_lossy(&resp).contains("recreate the key"));
+}
+
+async fn check_share_storage(config: &Config) {
+    let app_data = common::setup_with_config(config).await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+
+    let (resp, status) = post_request(&app, "/keys/check", None, Some(&key.key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let check: KeysCheckResponse = serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert!(check.valid && check.cloud);
+
+    let address = get_address(&app, &key.key).await;
+    assert_eq!(address, get_address(&app, &key.key).await);
+}
+
+#[tokio::test]
+async fn test_share_storage_backends() {
+    let dir = std::env::temp_dir().join(format!("kms-shares-{}", uuid::Uuid::new_v4()));
+
+    check_share_storage(&Config {
+        share_storage: ShareStorageKind::Memory,
+        ..common::config()
+    })
+    .await;
+
+    check_share_storage(&Config {
+        share_storage: ShareStorageKind::File,
+        share_storage_dir: Some(dir.to_str().unwrap().to_string()),
+        share_storage_key: Some("11".repeat(32)),
+        ..common::config()
+    })
+    .await;
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires Postgres and a Vault transit engine"]
+async fn test_transit_postgres_share_storage() {
+    check_share_storage(&Config {
+        share_storage: ShareStorageKind::TransitPostgres,
+        share_storage_transit_key: Some("kms".to_string()),
+        ..Default::default()
+    })
+    .await;
+}
+
+// Runs when PKCS11_MODULE is set, e.g. after
+// softhsm2-util --init-token --free --label kms --pin 1234 --so-pin 1234, with
+// PKCS11_MODULE pointing to libsofthsm2.so, PKCS11_TOKEN=kms and PKCS11_PIN=1234.
+#[tokio::test]
+async fn test_pkcs11_share_storage() {
+    let Ok(module) = std::env::var("PKCS11_MODULE") else {
+        eprintln!("PKCS11_MODULE is not set, skipping the PKCS#11 share storage");
+        return;
+    };
+
+    check_share_storage(&Config {
+        share_storage: ShareStorageKind::Pkcs11,
+        pkcs11_module: Some(module),
+        pkcs11_token: std::env::var("PKCS11_TOKEN").ok(),
+        pkcs11_pin: std::env::var("PKCS11_PIN").ok(),
+        ..common::config()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_signer_cache() {
+    let app_data = common::setup_with_config(&Config {
+        signer_cache_ttl: Some(60),
+        ..Config::in_memory()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (
+        secret,
+        KeysGenerateResponse {
+            key: uncached,
+            key_id: uncached_id,
+            ..
+        },
+    ) = create_user_and_key(&app).await;
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/generate",
+        Some(KeysGenerateRequest {
+            allow_signer_cache: true,
+            ..Default::default()
+        }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = post_request(&app, "/keys/grant", Some(&secret), Some(&key))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse {
+        key: granted,
+        id: granted_id,
+        ..
+    } = serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let sign = |key: String| {
+        let app = &app;
+        async move {
+            post_request_with_data(
+                app,
+                "/sign_message",
+                Some(SignMessageRequest {
+                    message: "Hello, world!".to_string(),
+                    ..Default::default()
+                }),
+                None,
+                Some(&key),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    let (resp, status) = sign(granted.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let SignMessageResponse { signature } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    let (resp, status) = sign(granted.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let SignMessageResponse { signature: cached } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+    assert_eq!(signature, cached);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/revoke",
+        Some(KeysRevokeRequest { id: granted_id }),
+        Some(&secret),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(sign(granted).await.1, StatusCode::BAD_REQUEST);
+
+    // Replacing the shares of the key drops its signers as well.
+    assert_eq!(sign(key.clone()).await.1, StatusCode::OK);
+
+    let (_resp, status) = post_request_with_data(
+        &app,
+        "/keys/refresh",
+        Some(KeysRefreshRequest::default()),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(sign(key).await.1, StatusCode::BAD_REQUEST);
+
+    // Keys that did not opt in are restored on every request.
+    assert_eq!(sign(uncached.clone()).await.1, StatusCode::OK);
+
+    keys::Entity::update_many()
+        .col_expr(keys::Column::LocalKey, Expr::value("01"))
+        .filter(keys::Column::Id.eq(uncached_id))
+        .exec(app_data.get_db_connection())
+        .await
+        .unwrap();
+
+    assert_eq!(sign(uncached).await.1, StatusCode::BAD_REQUEST);
+}
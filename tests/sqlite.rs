@@ -0,0 +1,57 @@
+use actix_http::StatusCode;
+use actix_web::{test, web, App};
+
+use kms::{handlers, AppData, KeysGenerateResponse, KeysRefreshRequest, SignMessageRequest};
+use migration::{Migrator, MigratorTrait};
+
+use crate::common::{create_user_and_key, post_request_with_data};
+
+mod common;
+
+#[tokio::test]
+async fn test_sqlite() {
+    let app_data = AppData::in_memory().await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (secret, KeysGenerateResponse { key, .. }) = create_user_and_key(&app).await;
+
+    let (resp, status) = post_request_with_data(
+        &app,
+        "/keys/refresh",
+        Some(KeysRefreshRequest::default()),
+        Some(&secret),
+        Some(&key),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let KeysGenerateResponse { key: refreshed, .. } =
+        serde_json::from_slice(&resp).expect("Failed to parse response");
+
+    for (key, expected) in [(refreshed, StatusCode::OK), (key, StatusCode::BAD_REQUEST)] {
+        let (_resp, status) = post_request_with_data(
+            &app,
+            "/sign_message",
+            Some(SignMessageRequest {
+                message: "Hello, world!".to_string(),
+                ..Default::default()
+            }),
+            None,
+            Some(&key),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, expected);
+    }
+
+    Migrator::down(app_data.get_db_connection(), None)
+        .await
+        .expect("migration error");
+}
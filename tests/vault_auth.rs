@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use actix_http::{Request, StatusCode};
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, web, App, Error};
+use vaultrs::api::auth::approle::requests::{
+    GenerateNewSecretIDRequestBuilder, SetAppRoleRequestBuilder,
+};
+use vaultrs::api::token::requests::CreateTokenRequestBuilder;
+use vaultrs::auth::approle;
+use vaultrs::client::{VaultClient, VaultClientSettingsBuilder};
+use vaultrs::{sys, token};
+
+use kms::{handlers, Config, SignMessageRequest, VaultAuthMethod};
+
+use crate::common::{create_user_and_key, post_request_with_data};
+
+mod common;
+
+fn root_client() -> VaultClient {
+    let config = Config::default();
+
+    let mut settings = VaultClientSettingsBuilder::default();
+    settings.address(config.vault_storage.expect("VAULT_STORAGE is required"));
+    settings.token(config.vault_token.expect("VAULT_TOKEN is required"));
+    VaultClient::new(settings.build().unwrap()).unwrap()
+}
+
+/// Creates an AppRole with the root token and returns its role and secret IDs.
+async fn approle_credentials(role: &mut SetAppRoleRequestBuilder, name: &str) -> (String, String) {
+    let root = root_client();
+
+    // Fails when already enabled by a previous run.
+    let _ = sys::auth::enable(&root, "approle", "approle", None).await;
+
+    approle::role::set(&root, "approle", name, Some(role))
+        .await
+        .unwrap();
+
+    let role_id = approle::role::read_id(&root, "approle", name)
+        .await
+        .unwrap()
+        .role_id;
+    let secret_id = approle::role::secret::generate(
+        &root,
+        "approle",
+        name,
+        Some(&mut GenerateNewSecretIDRequestBuilder::default()),
+    )
+    .await
+    .unwrap()
+    .secret_id;
+
+    (role_id, secret_id)
+}
+
+async fn healthcheck<T>(app: &T) -> StatusCode
+where
+    T: Service<Request, Response = ServiceResponse, Error = Error>,
+{
+    let req = test::TestRequest::get().uri("/").to_request();
+    app.call(req).await.unwrap().status()
+}
+
+async fn sign<T>(app: &T, key: &str) -> StatusCode
+where
+    T: Service<Request, Response = ServiceResponse, Error = Error>,
+{
+    let message = SignMessageRequest {
+        message: "Hello, world!".to_string(),
+        ..Default::default()
+    };
+
+    let (_resp, status) = post_request_with_data(app, "/sign_message", &message, None, Some(key))
+        .await
+        .unwrap();
+    status
+}
+
+#[tokio::test]
+#[ignore = "requires Vault with the AppRole auth method"]
+async fn test_approle_token_renewal() {
+    let (role_id, secret_id) = approle_credentials(
+        SetAppRoleRequestBuilder::default()
+            .token_ttl("2s")
+            .token_max_ttl("1h"),
+        "kms-renewal",
+    )
+    .await;
+
+    let app_data = common::setup_with_config(&Config {
+        vault_auth: VaultAuthMethod::AppRole,
+        vault_role_id: Some(role_id),
+        vault_secret_id: Some(secret_id),
+        ..Default::default()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    assert_eq!(sign(&app, &key.key).await, StatusCode::OK);
+
+    // Past the TTL of the first token.
+    tokio::time::sleep(Duration::from_secs(4)).await;
+
+    assert_eq!(healthcheck(&app).await, StatusCode::OK);
+    assert_eq!(sign(&app, &key.key).await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "requires Vault with the AppRole auth method"]
+async fn test_approle_token_expiry() {
+    // The token can not be renewed past its max TTL, and the secret ID can not log in again.
+    let (role_id, secret_id) = approle_credentials(
+        SetAppRoleRequestBuilder::default()
+            .token_ttl("2s")
+            .token_max_ttl("2s")
+            .secret_id_num_uses(1u64),
+        "kms-expiry",
+    )
+    .await;
+
+    let app_data = common::setup_with_config(&Config {
+        vault_auth: VaultAuthMethod::AppRole,
+        vault_role_id: Some(role_id),
+        vault_secret_id: Some(secret_id),
+        ..Default::default()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    assert_eq!(healthcheck(&app).await, StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    assert_eq!(healthcheck(&app).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_ne!(sign(&app, &key.key).await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "requires Vault"]
+async fn test_static_token_expiry() {
+    // Not renewable, the token is used until it expires and no renewal is attempted.
+    let auth = token::new(
+        &root_client(),
+        Some(
+            CreateTokenRequestBuilder::default()
+                .ttl("2s")
+                .renewable(false),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let app_data = common::setup_with_config(&Config {
+        vault_auth: VaultAuthMethod::Token,
+        vault_token: Some(auth.client_token),
+        ..Default::default()
+    })
+    .await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_data.clone()))
+            .configure(handlers),
+    )
+    .await;
+
+    let (_, key) = create_user_and_key(&app).await;
+    assert_eq!(sign(&app, &key.key).await, StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    assert_eq!(healthcheck(&app).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_ne!(sign(&app, &key.key).await, StatusCode::OK);
+}